@@ -0,0 +1,77 @@
+// Turning clicks and scrolls on tray items into the actions they're bound to in the config
+
+use std::time::{Duration, Instant};
+
+use iced::{
+    event,
+    keyboard::{self, Modifiers},
+    mouse::ScrollDelta,
+    Event, Subscription,
+};
+
+use crate::config::{MouseBindings, TrayAction};
+
+use super::AppTrayMessage;
+
+/// Touchpads scroll in lots of tiny steps, only one of them counts in this long
+const SCROLL_COOLDOWN: Duration = Duration::from_millis(200);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MouseInput {
+    Left,
+    Middle,
+    Right,
+    Scroll(ScrollDelta),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BindingState {
+    /// Modifiers held at the moment, as far as the compositor lets the panel know
+    modifiers: Modifiers,
+    last_scroll: Option<Instant>,
+}
+
+impl BindingState {
+    pub fn set_modifiers(&mut self, modifiers: Modifiers) {
+        self.modifiers = modifiers;
+    }
+
+    /// What `input` is bound to, scroll steps right after another one do nothing
+    pub fn action(&mut self, bindings: &MouseBindings, input: MouseInput) -> TrayAction {
+        match input {
+            MouseInput::Left if self.modifiers.shift() => bindings.shift_left,
+            MouseInput::Left => bindings.left,
+            MouseInput::Middle => bindings.middle,
+            MouseInput::Right => bindings.right,
+            MouseInput::Scroll(delta) => {
+                let y = match delta {
+                    ScrollDelta::Lines { y, .. } | ScrollDelta::Pixels { y, .. } => y,
+                };
+                // Sideways scrolling isn't bound to anything
+                let now = Instant::now();
+                if y == 0.0
+                    || self
+                        .last_scroll
+                        .is_some_and(|last| now.duration_since(last) < SCROLL_COOLDOWN)
+                {
+                    return TrayAction::None;
+                }
+                self.last_scroll = Some(now);
+                if y > 0.0 {
+                    bindings.scroll_up
+                } else {
+                    bindings.scroll_down
+                }
+            }
+        }
+    }
+}
+
+pub fn subscription() -> Subscription<AppTrayMessage> {
+    event::listen_with(|event, _status, _window| match event {
+        Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+            Some(AppTrayMessage::Modifiers(modifiers))
+        }
+        _ => None,
+    })
+}
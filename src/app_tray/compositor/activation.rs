@@ -0,0 +1,74 @@
+use smithay_client_toolkit::activation::{ActivationHandler, RequestData, RequestDataExt};
+use wayland_client::protocol::{wl_seat::WlSeat, wl_surface::WlSurface};
+
+use crate::launcher::{self, LaunchInfo};
+
+use super::{AppData, WaylandIncoming};
+
+/// Token request that remembers what to launch once the compositor answers
+pub(super) struct ExecRequestData {
+    data: RequestData,
+    info: LaunchInfo,
+}
+
+impl RequestDataExt for ExecRequestData {
+    fn app_id(&self) -> Option<&str> {
+        self.data.app_id()
+    }
+
+    fn seat_and_serial(&self) -> Option<(&WlSeat, u32)> {
+        self.data.seat_and_serial()
+    }
+
+    fn surface(&self) -> Option<&WlSurface> {
+        self.data.surface()
+    }
+}
+
+impl ActivationHandler for AppData {
+    type RequestData = ExecRequestData;
+
+    fn new_token(&mut self, token: String, data: &Self::RequestData) {
+        let _ = self.tx.unbounded_send(WaylandIncoming::LaunchToken {
+            app_id: data.info.app_id.clone(),
+            token: token.clone(),
+        });
+        self.spawn_exec(data.info.clone(), Some(token));
+    }
+}
+
+impl AppData {
+    pub(super) fn handle_token_request(&mut self, info: LaunchInfo) {
+        if let Some(activation_state) = self.activation_state.as_ref() {
+            // The panel never receives input serials on this connection, so the request
+            // is made without one, same as other shells do
+            activation_state.request_token_with_data(
+                &self.queue_handle,
+                ExecRequestData {
+                    data: RequestData {
+                        app_id: Some(info.app_id.clone()),
+                        seat_and_serial: None,
+                        surface: None,
+                    },
+                    info,
+                },
+            );
+        } else {
+            self.spawn_exec(info, None);
+        }
+    }
+
+    /// Launches off the wayland thread so a slow spawn doesn't stall toplevel updates
+    fn spawn_exec(&self, info: LaunchInfo, token: Option<String>) {
+        let tx = self.tx.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = launcher::launch(&info, token.as_deref()) {
+                let _ = tx.unbounded_send(WaylandIncoming::LaunchFailed {
+                    app_id: info.app_id,
+                    token,
+                    error: e.to_string(),
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,818 @@
+// Window thumbnails through ext-image-copy-capture-v1, copied into wl_shm buffers so they can
+// be shown without a GPU. Capture sources are made from ext-foreign-toplevel-list handles, when
+// windows come from another protocol the ext list is bound just for capturing and its toplevels
+// are matched up with theirs by app_id and title
+
+use std::{collections::HashMap, fmt, time::Duration};
+
+use smithay_client_toolkit::{
+    reexports::calloop::{
+        timer::{TimeoutAction, Timer},
+        LoopHandle,
+    },
+    shm::{raw::RawPool, Shm, ShmHandler},
+};
+use wayland_client::{
+    event_created_child,
+    globals::GlobalList,
+    protocol::{wl_buffer, wl_shm},
+    Connection, Dispatch, Proxy, QueueHandle, WEnum,
+};
+use wayland_protocols::ext::{
+    foreign_toplevel_list::v1::client::{
+        ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+        ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+    },
+    image_capture_source::v1::client::{
+        ext_foreign_toplevel_image_capture_source_manager_v1, ext_image_capture_source_v1,
+    },
+    image_copy_capture::v1::client::{
+        ext_image_copy_capture_frame_v1, ext_image_copy_capture_manager_v1,
+        ext_image_copy_capture_session_v1,
+    },
+};
+
+use super::{AppData, ToplevelHandle, WaylandIncoming};
+
+/// Thumbnails are scaled down to fit in this size
+pub const THUMBNAIL_WIDTH: u32 = 240;
+pub const THUMBNAIL_HEIGHT: u32 = 135;
+
+/// Time between captures of the same window, every copy is done on the CPU
+const CAPTURE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Formats that can be turned into RGBA, every compositor has to support the first two
+const SUPPORTED_FORMATS: [wl_shm::Format; 4] = [
+    wl_shm::Format::Xrgb8888,
+    wl_shm::Format::Argb8888,
+    wl_shm::Format::Xbgr8888,
+    wl_shm::Format::Abgr8888,
+];
+
+/// Scaled down RGBA pixels of a window
+#[derive(Clone)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl fmt::Debug for Thumbnail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Thumbnail")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+
+struct ShmBuffer {
+    pool: RawPool,
+    buffer: wl_buffer::WlBuffer,
+    width: u32,
+    height: u32,
+    format: wl_shm::Format,
+}
+
+impl Drop for ShmBuffer {
+    fn drop(&mut self) {
+        // The pool destroys itself
+        self.buffer.destroy();
+    }
+}
+
+/// What the session asks of buffers, sent before every `done`
+#[derive(Default)]
+struct BufferConstraints {
+    size: Option<(u32, u32)>,
+    formats: Vec<wl_shm::Format>,
+}
+
+struct CaptureSession {
+    /// Toplevel the thumbnails are for, as the rest of the panel knows it
+    toplevel: ToplevelHandle,
+    session: ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+    pending: BufferConstraints,
+    current: BufferConstraints,
+    buffer: Option<ShmBuffer>,
+    frame: Option<ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1>,
+    /// A capture is waiting for the interval to pass
+    scheduled: bool,
+}
+
+impl Drop for CaptureSession {
+    fn drop(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            frame.destroy();
+        }
+        self.session.destroy();
+    }
+}
+
+/// User data of the ext list when it's only bound for capturing, so its toplevels stay out of
+/// the tray
+pub(super) struct CaptureOnly;
+
+/// What toplevels from the capture-only list are matched up by
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct CaptureToplevel {
+    app_id: String,
+    title: String,
+}
+
+pub(super) struct CaptureState {
+    shm: Shm,
+    source_manager:
+        ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+    copy_manager: ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1,
+    loop_handle: LoopHandle<'static, AppData>,
+    sessions: HashMap<ExtForeignToplevelHandleV1, CaptureSession>,
+    /// Kept alive so its toplevels keep coming
+    _capture_list: Option<ExtForeignToplevelListV1>,
+    /// Toplevels of the capture-only list, with their pending and current state
+    toplevels: Vec<(
+        ExtForeignToplevelHandleV1,
+        CaptureToplevel,
+        Option<CaptureToplevel>,
+    )>,
+}
+
+impl CaptureState {
+    /// `None` when the compositor can't capture toplevels. `bind_list` binds the ext list just
+    /// for capturing, for when windows come from another protocol
+    pub(super) fn bind(
+        globals: &GlobalList,
+        qh: &QueueHandle<AppData>,
+        loop_handle: LoopHandle<'static, AppData>,
+        bind_list: bool,
+    ) -> Option<Self> {
+        let source_manager = match globals.bind::<ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1, _, _>(qh, 1..=1, ()) {
+            Ok(manager) => manager,
+            Err(e) => {
+                log::info!(
+                    "[PROTOCOL] ext_foreign_toplevel_image_capture_source_manager_v1 could not be bound: {}",
+                    e
+                );
+                return None;
+            }
+        };
+        let copy_manager = match globals
+            .bind::<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, _, _>(
+            qh,
+            1..=1,
+            (),
+        ) {
+            Ok(manager) => manager,
+            Err(e) => {
+                log::info!(
+                    "[PROTOCOL] ext_image_copy_capture_manager_v1 could not be bound: {}",
+                    e
+                );
+                return None;
+            }
+        };
+        let capture_list = if bind_list {
+            match globals.bind::<ExtForeignToplevelListV1, _, _>(qh, 1..=1, CaptureOnly) {
+                Ok(list) => Some(list),
+                Err(e) => {
+                    log::info!(
+                        "[PROTOCOL] ext_foreign_toplevel_list_v1 could not be bound for capturing: {}",
+                        e
+                    );
+                    return None;
+                }
+            }
+        } else {
+            None
+        };
+        // Bound last, wl_shm events are only handled once the capture state exists
+        let shm = match Shm::bind(globals, qh) {
+            Ok(shm) => shm,
+            Err(e) => {
+                log::info!("[PROTOCOL] wl_shm could not be bound: {}", e);
+                return None;
+            }
+        };
+        Some(Self {
+            shm,
+            source_manager,
+            copy_manager,
+            loop_handle,
+            sessions: HashMap::new(),
+            _capture_list: capture_list,
+            toplevels: Vec::new(),
+        })
+    }
+}
+
+impl AppData {
+    /// Capture exactly `handles`, stopping captures of everything else
+    pub(super) fn handle_capture_request(&mut self, handles: Vec<ToplevelHandle>) {
+        // Only ext toplevels can be turned into capture sources
+        let targets = handles
+            .into_iter()
+            .filter_map(|toplevel| {
+                let source = self.capture_source(&toplevel);
+                if source.is_none() {
+                    log::debug!("No ext toplevel to capture {:?} through", toplevel);
+                }
+                Some((source?, toplevel))
+            })
+            .collect::<Vec<_>>();
+        let Some(capture_state) = self.capture_state.as_mut() else {
+            return;
+        };
+        capture_state
+            .sessions
+            .retain(|handle, _| targets.iter().any(|(target, _)| target == handle));
+        for (handle, toplevel) in targets {
+            if capture_state.sessions.contains_key(&handle) {
+                continue;
+            }
+            let source =
+                capture_state
+                    .source_manager
+                    .create_source(&handle, &self.queue_handle, ());
+            let session = capture_state.copy_manager.create_session(
+                &source,
+                ext_image_copy_capture_manager_v1::Options::empty(),
+                &self.queue_handle,
+                handle.clone(),
+            );
+            // The session keeps capturing without its source
+            source.destroy();
+            capture_state.sessions.insert(
+                handle,
+                CaptureSession {
+                    toplevel,
+                    session,
+                    pending: BufferConstraints::default(),
+                    current: BufferConstraints::default(),
+                    buffer: None,
+                    frame: None,
+                    scheduled: false,
+                },
+            );
+        }
+    }
+
+    /// The ext handle `toplevel` is captured through. Toplevels from other protocols are
+    /// matched up with the capture-only list by app_id and title
+    fn capture_source(&self, toplevel: &ToplevelHandle) -> Option<ExtForeignToplevelHandleV1> {
+        if let ToplevelHandle::Ext(handle) = toplevel {
+            return Some(handle.clone());
+        }
+        let capture_state = self.capture_state.as_ref()?;
+        let windows = self
+            .toplevel_state
+            .toplevels
+            .iter()
+            .filter_map(|(_, data)| data.current_info.as_ref())
+            .map(|info| CaptureToplevel {
+                app_id: info.app_id.clone(),
+                title: info.title.clone(),
+            })
+            .collect::<Vec<_>>();
+        let info = self
+            .toplevel_state
+            .toplevels
+            .iter()
+            .find(|(handle, _)| handle == toplevel)?
+            .1
+            .current_info
+            .as_ref()?;
+        let wanted = CaptureToplevel {
+            app_id: info.app_id.clone(),
+            title: info.title.clone(),
+        };
+        match_toplevel(&wanted, &windows, &capture_state.toplevels)
+    }
+
+    /// Stop capturing windows that no longer match the toplevel they're captured through,
+    /// like after one of them changed its title
+    pub(super) fn drop_mismatched_sessions(&mut self) {
+        let Some(capture_state) = self.capture_state.as_ref() else {
+            return;
+        };
+        let mismatched = capture_state
+            .sessions
+            .iter()
+            .filter(|(handle, session)| {
+                self.capture_source(&session.toplevel).as_ref() != Some(*handle)
+            })
+            .map(|(handle, _)| handle.clone())
+            .collect::<Vec<_>>();
+        if let Some(capture_state) = self.capture_state.as_mut() {
+            for handle in mismatched {
+                log::debug!("{:?} no longer matches the window it captured", handle.id());
+                capture_state.sessions.remove(&handle);
+            }
+        }
+    }
+
+    /// Make sure the session's buffer matches its constraints, then capture into it
+    fn capture_frame(&mut self, handle: &ExtForeignToplevelHandleV1) {
+        let Some(capture_state) = self.capture_state.as_mut() else {
+            return;
+        };
+        let Some(session) = capture_state.sessions.get_mut(handle) else {
+            return;
+        };
+        if session.frame.is_some() {
+            return;
+        }
+        let Some((width, height)) = session.current.size else {
+            return;
+        };
+        let Some(format) = SUPPORTED_FORMATS
+            .into_iter()
+            .find(|format| session.current.formats.contains(format))
+        else {
+            log::warn!("No usable shm format to capture {:?}", handle.id());
+            return;
+        };
+        let is_current = session.buffer.as_ref().is_some_and(|buffer| {
+            buffer.width == width && buffer.height == height && buffer.format == format
+        });
+        if !is_current {
+            session.buffer = None;
+            let stride = width * 4;
+            let mut pool = match RawPool::new((stride * height) as usize, &capture_state.shm) {
+                Ok(pool) => pool,
+                Err(e) => {
+                    log::warn!("Could not create a capture buffer: {}", e);
+                    return;
+                }
+            };
+            let buffer = pool.create_buffer(
+                0,
+                width as i32,
+                height as i32,
+                stride as i32,
+                format,
+                (),
+                &self.queue_handle,
+            );
+            session.buffer = Some(ShmBuffer {
+                pool,
+                buffer,
+                width,
+                height,
+                format,
+            });
+        }
+        let buffer = session.buffer.as_ref().unwrap();
+        let frame = session
+            .session
+            .create_frame(&self.queue_handle, handle.clone());
+        frame.attach_buffer(&buffer.buffer);
+        frame.damage_buffer(0, 0, width as i32, height as i32);
+        frame.capture();
+        session.frame = Some(frame);
+    }
+
+    /// Capture again once the interval has passed
+    fn schedule_capture(&mut self, handle: ExtForeignToplevelHandleV1) {
+        let Some(capture_state) = self.capture_state.as_mut() else {
+            return;
+        };
+        let Some(session) = capture_state.sessions.get_mut(&handle) else {
+            return;
+        };
+        if session.scheduled {
+            return;
+        }
+        session.scheduled = true;
+        let timer = Timer::from_duration(CAPTURE_INTERVAL);
+        let _ = capture_state
+            .loop_handle
+            .insert_source(timer, move |_, _, state| {
+                if let Some(session) = state
+                    .capture_state
+                    .as_mut()
+                    .and_then(|capture_state| capture_state.sessions.get_mut(&handle))
+                {
+                    session.scheduled = false;
+                    state.capture_frame(&handle);
+                }
+                TimeoutAction::Drop
+            });
+    }
+
+    fn frame_ready(&mut self, handle: &ExtForeignToplevelHandleV1) {
+        let Some(session) = self
+            .capture_state
+            .as_mut()
+            .and_then(|capture_state| capture_state.sessions.get_mut(handle))
+        else {
+            return;
+        };
+        if let Some(frame) = session.frame.take() {
+            frame.destroy();
+        }
+        if let Some(buffer) = session.buffer.as_mut() {
+            let thumbnail = scale_to_rgba(
+                buffer.pool.mmap(),
+                buffer.width,
+                buffer.height,
+                buffer.format,
+            );
+            let _ = self.tx.unbounded_send(WaylandIncoming::Thumbnail(
+                session.toplevel.clone(),
+                thumbnail,
+            ));
+        }
+        self.schedule_capture(handle.clone());
+    }
+
+    fn frame_failed(
+        &mut self,
+        handle: &ExtForeignToplevelHandleV1,
+        reason: WEnum<ext_image_copy_capture_frame_v1::FailureReason>,
+    ) {
+        let Some(capture_state) = self.capture_state.as_mut() else {
+            return;
+        };
+        let Some(session) = capture_state.sessions.get_mut(handle) else {
+            return;
+        };
+        if let Some(frame) = session.frame.take() {
+            frame.destroy();
+        }
+        match reason {
+            // New constraints are on their way, the next done captures again
+            WEnum::Value(ext_image_copy_capture_frame_v1::FailureReason::BufferConstraints) => {}
+            WEnum::Value(ext_image_copy_capture_frame_v1::FailureReason::Stopped) => {
+                capture_state.sessions.remove(handle);
+            }
+            _ => {
+                log::debug!("Capturing {:?} failed: {:?}", handle.id(), reason);
+                self.schedule_capture(handle.clone());
+            }
+        }
+    }
+}
+
+/// The capture-only toplevel that is `wanted`, one of `windows`. Windows that share their
+/// app_id and title with another one, on either side, can't be told apart and aren't matched
+fn match_toplevel<H: Clone>(
+    wanted: &CaptureToplevel,
+    windows: &[CaptureToplevel],
+    toplevels: &[(H, CaptureToplevel, Option<CaptureToplevel>)],
+) -> Option<H> {
+    if windows.iter().filter(|window| *window == wanted).count() != 1 {
+        return None;
+    }
+    let mut matches = toplevels
+        .iter()
+        .filter(|(_, _, current)| current.as_ref() == Some(wanted));
+    let (handle, _, _) = matches.next()?;
+    matches.next().is_none().then(|| handle.clone())
+}
+
+/// Scale the captured pixels down to thumbnail size, picking the nearest pixel
+fn scale_to_rgba(data: &[u8], width: u32, height: u32, format: wl_shm::Format) -> Thumbnail {
+    let scale = (THUMBNAIL_WIDTH as f32 / width as f32)
+        .min(THUMBNAIL_HEIGHT as f32 / height as f32)
+        .min(1.0);
+    let thumbnail_width = ((width as f32 * scale) as u32).max(1);
+    let thumbnail_height = ((height as f32 * scale) as u32).max(1);
+    let mut pixels = Vec::with_capacity((thumbnail_width * thumbnail_height * 4) as usize);
+    for y in 0..thumbnail_height {
+        let source_y = (y * height / thumbnail_height) as usize;
+        for x in 0..thumbnail_width {
+            let source_x = (x * width / thumbnail_width) as usize;
+            let offset = (source_y * width as usize + source_x) * 4;
+            let Some(&[b0, b1, b2, b3]) = data.get(offset..offset + 4) else {
+                pixels.extend_from_slice(&[0, 0, 0, 0]);
+                continue;
+            };
+            // Formats are little endian, so the bytes are in reverse order of the name
+            let rgba = match format {
+                wl_shm::Format::Argb8888 => [b2, b1, b0, b3],
+                wl_shm::Format::Abgr8888 => [b0, b1, b2, b3],
+                wl_shm::Format::Xbgr8888 => [b0, b1, b2, 255],
+                _ => [b2, b1, b0, 255],
+            };
+            pixels.extend_from_slice(&rgba);
+        }
+    }
+    Thumbnail {
+        width: thumbnail_width,
+        height: thumbnail_height,
+        pixels,
+    }
+}
+
+impl ShmHandler for AppData {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self
+            .capture_state
+            .as_mut()
+            .expect("wl_shm is only bound for capturing")
+            .shm
+    }
+}
+
+smithay_client_toolkit::delegate_shm!(AppData);
+
+impl Dispatch<wl_buffer::WlBuffer, ()> for AppData {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_buffer::WlBuffer,
+        _event: wl_buffer::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        // Buffers are only reused once their frame is done with them
+    }
+}
+
+impl Dispatch<ext_image_capture_source_v1::ExtImageCaptureSourceV1, ()> for AppData {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ext_image_capture_source_v1::ExtImageCaptureSourceV1,
+        _event: <ext_image_capture_source_v1::ExtImageCaptureSourceV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl
+    Dispatch<
+        ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+        (),
+    > for AppData
+{
+    fn event(
+        _state: &mut Self,
+        _proxy: &ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+        _event: <ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1, ()> for AppData {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1,
+        _event: <ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl
+    Dispatch<
+        ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+        ExtForeignToplevelHandleV1,
+    > for AppData
+{
+    fn event(
+        state: &mut Self,
+        _proxy: &ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1,
+        event: <ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1 as Proxy>::Event,
+        handle: &ExtForeignToplevelHandleV1,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        log::trace!("ext_image_copy_capture_session_v1::event: {:?}", event);
+        let Some(capture_state) = state.capture_state.as_mut() else {
+            return;
+        };
+        let Some(session) = capture_state.sessions.get_mut(handle) else {
+            return;
+        };
+        match event {
+            ext_image_copy_capture_session_v1::Event::BufferSize { width, height } => {
+                session.pending.size = Some((width, height));
+            }
+            ext_image_copy_capture_session_v1::Event::ShmFormat {
+                format: WEnum::Value(format),
+            } => {
+                session.pending.formats.push(format);
+            }
+            ext_image_copy_capture_session_v1::Event::Done => {
+                // Constraints are sent in full before every done
+                session.current = std::mem::take(&mut session.pending);
+                state.capture_frame(handle);
+            }
+            ext_image_copy_capture_session_v1::Event::Stopped => {
+                capture_state.sessions.remove(handle);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl
+    Dispatch<
+        ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+        ExtForeignToplevelHandleV1,
+    > for AppData
+{
+    fn event(
+        state: &mut Self,
+        _proxy: &ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1,
+        event: <ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1 as Proxy>::Event,
+        handle: &ExtForeignToplevelHandleV1,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_image_copy_capture_frame_v1::Event::Ready => state.frame_ready(handle),
+            ext_image_copy_capture_frame_v1::Event::Failed { reason } => {
+                state.frame_failed(handle, reason)
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtForeignToplevelListV1, CaptureOnly> for AppData {
+    fn event(
+        state: &mut Self,
+        _proxy: &ExtForeignToplevelListV1,
+        event: ext_foreign_toplevel_list_v1::Event,
+        _data: &CaptureOnly,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } = event {
+            if let Some(capture_state) = state.capture_state.as_mut() {
+                capture_state
+                    .toplevels
+                    .push((toplevel, CaptureToplevel::default(), None));
+            }
+        }
+    }
+
+    event_created_child!(AppData, ExtForeignToplevelListV1, [
+        ext_foreign_toplevel_list_v1::EVT_TOPLEVEL_OPCODE => (ExtForeignToplevelHandleV1, CaptureOnly)
+    ]);
+}
+
+impl Dispatch<ExtForeignToplevelHandleV1, CaptureOnly> for AppData {
+    fn event(
+        state: &mut Self,
+        toplevel: &ExtForeignToplevelHandleV1,
+        event: ext_foreign_toplevel_handle_v1::Event,
+        _data: &CaptureOnly,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        let Some(capture_state) = state.capture_state.as_mut() else {
+            return;
+        };
+        let Some(idx) = capture_state
+            .toplevels
+            .iter()
+            .position(|(handle, _, _)| handle == toplevel)
+        else {
+            return;
+        };
+        let (_, pending, current) = &mut capture_state.toplevels[idx];
+        match event {
+            ext_foreign_toplevel_handle_v1::Event::AppId { app_id } => pending.app_id = app_id,
+            ext_foreign_toplevel_handle_v1::Event::Title { title } => pending.title = title,
+            ext_foreign_toplevel_handle_v1::Event::Done => {
+                *current = Some(pending.clone());
+                state.drop_mismatched_sessions();
+            }
+            ext_foreign_toplevel_handle_v1::Event::Closed => {
+                capture_state.toplevels.remove(idx);
+                capture_state.sessions.remove(toplevel);
+                toplevel.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toplevel(app_id: &str, title: &str) -> CaptureToplevel {
+        CaptureToplevel {
+            app_id: app_id.to_string(),
+            title: title.to_string(),
+        }
+    }
+
+    #[test]
+    fn match_toplevels() {
+        let terminal = toplevel("org.example.Terminal", "~");
+        let editor = toplevel("org.example.Editor", "~");
+        let done =
+            |id: u32, toplevel: &CaptureToplevel| (id, toplevel.clone(), Some(toplevel.clone()));
+        let cases = [
+            (
+                "unique",
+                vec![terminal.clone(), editor.clone()],
+                vec![done(1, &editor), done(2, &terminal)],
+                Some(2),
+            ),
+            (
+                "two windows with the same title",
+                vec![terminal.clone(), terminal.clone()],
+                vec![done(1, &terminal), done(2, &terminal)],
+                None,
+            ),
+            (
+                "same title twice in the capture list",
+                vec![terminal.clone()],
+                vec![done(1, &terminal), done(2, &terminal)],
+                None,
+            ),
+            (
+                "title changed on one side",
+                vec![terminal.clone()],
+                vec![done(1, &toplevel("org.example.Terminal", "vim"))],
+                None,
+            ),
+            (
+                "only pending in the capture list",
+                vec![terminal.clone()],
+                vec![(1, terminal.clone(), None)],
+                None,
+            ),
+            (
+                "nothing to capture",
+                vec![terminal.clone()],
+                Vec::new(),
+                None,
+            ),
+        ];
+        for (name, windows, toplevels, expected) in cases {
+            assert_eq!(
+                match_toplevel(&terminal, &windows, &toplevels),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn convert_shm_formats() {
+        // One pixel, bytes as they're laid out in memory
+        let pixel = [0x11, 0x22, 0x33, 0x44];
+        let cases = [
+            (wl_shm::Format::Argb8888, [0x33, 0x22, 0x11, 0x44]),
+            (wl_shm::Format::Xrgb8888, [0x33, 0x22, 0x11, 0xff]),
+            (wl_shm::Format::Abgr8888, [0x11, 0x22, 0x33, 0x44]),
+            (wl_shm::Format::Xbgr8888, [0x11, 0x22, 0x33, 0xff]),
+        ];
+        for (format, rgba) in cases {
+            let thumbnail = scale_to_rgba(&pixel, 1, 1, format);
+            assert_eq!(thumbnail.pixels, rgba, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn scale_down_to_fit() {
+        // Red on the left half, blue on the right
+        let (width, height) = (THUMBNAIL_WIDTH * 4, THUMBNAIL_HEIGHT * 2);
+        let data = (0..height)
+            .flat_map(|_| {
+                (0..width).flat_map(|x| {
+                    if x < width / 2 {
+                        [0x00, 0x00, 0xff, 0xff]
+                    } else {
+                        [0xff, 0x00, 0x00, 0xff]
+                    }
+                })
+            })
+            .collect::<Vec<u8>>();
+        let thumbnail = scale_to_rgba(&data, width, height, wl_shm::Format::Argb8888);
+        // Only as tall as the width allows, to keep the aspect ratio
+        assert_eq!(thumbnail.width, THUMBNAIL_WIDTH);
+        assert_eq!(thumbnail.height, THUMBNAIL_HEIGHT / 2);
+        assert_eq!(
+            thumbnail.pixels.len(),
+            (thumbnail.width * thumbnail.height * 4) as usize
+        );
+        assert_eq!(thumbnail.pixels[..4], [0xff, 0x00, 0x00, 0xff]);
+        let last = thumbnail.pixels.len() - 4;
+        assert_eq!(thumbnail.pixels[last..], [0x00, 0x00, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn small_and_short_buffers() {
+        // Small windows aren't scaled up
+        let thumbnail = scale_to_rgba(&[0; 2 * 3 * 4], 2, 3, wl_shm::Format::Xrgb8888);
+        assert_eq!((thumbnail.width, thumbnail.height), (2, 3));
+        // Pixels missing from the buffer come out transparent
+        let thumbnail = scale_to_rgba(&[0xff; 4], 2, 1, wl_shm::Format::Argb8888);
+        assert_eq!(thumbnail.pixels, [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]);
+    }
+}
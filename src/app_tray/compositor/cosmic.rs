@@ -3,20 +3,29 @@ use std::collections::HashSet;
 use cosmic_protocols::{
     toplevel_info::v1::client::{zcosmic_toplevel_handle_v1, zcosmic_toplevel_info_v1},
     toplevel_management::v1::client::zcosmic_toplevel_manager_v1,
+    workspace::v1::client::{
+        zcosmic_workspace_group_handle_v1, zcosmic_workspace_handle_v1,
+        zcosmic_workspace_manager_v1,
+    },
 };
-use wayland_client::{Connection, Dispatch, Proxy, QueueHandle};
+use wayland_client::{event_created_child, Connection, Dispatch, Proxy, QueueHandle};
 
-use super::{AppData, ToplevelHandle, ToplevelHandleEvent, ToplevelManagerEvent, ToplevelState};
+use super::{
+    workspace::{
+        WorkspaceGroupEvent, WorkspaceGroupHandle, WorkspaceHandle, WorkspaceHandleEvent,
+        WorkspaceManagerEvent, WorkspaceState,
+    },
+    AppData, ToplevelHandle, ToplevelHandleEvent, ToplevelManagerEvent, ToplevelState,
+};
 
-impl From<zcosmic_toplevel_handle_v1::State> for ToplevelState {
-    fn from(value: zcosmic_toplevel_handle_v1::State) -> Self {
-        match value {
-            zcosmic_toplevel_handle_v1::State::Maximized => Self::Maximized,
-            zcosmic_toplevel_handle_v1::State::Minimized => Self::Minimized,
-            zcosmic_toplevel_handle_v1::State::Activated => Self::Activated,
-            zcosmic_toplevel_handle_v1::State::Fullscreen => Self::Fullscreen,
-            _ => todo!(),
-        }
+/// States the tray has no use for, like sticky, are left out
+fn toplevel_state(value: zcosmic_toplevel_handle_v1::State) -> Option<ToplevelState> {
+    match value {
+        zcosmic_toplevel_handle_v1::State::Maximized => Some(ToplevelState::Maximized),
+        zcosmic_toplevel_handle_v1::State::Minimized => Some(ToplevelState::Minimized),
+        zcosmic_toplevel_handle_v1::State::Activated => Some(ToplevelState::Activated),
+        zcosmic_toplevel_handle_v1::State::Fullscreen => Some(ToplevelState::Fullscreen),
+        _ => None,
     }
 }
 
@@ -37,20 +46,108 @@ impl From<zcosmic_toplevel_handle_v1::Event> for ToplevelHandleEvent {
             zcosmic_toplevel_handle_v1::Event::OutputLeave { output } => {
                 ToplevelHandleEvent::OutputLeave { output }
             }
-            zcosmic_toplevel_handle_v1::Event::WorkspaceEnter { .. } => todo!(),
-            zcosmic_toplevel_handle_v1::Event::WorkspaceLeave { .. } => todo!(),
+            zcosmic_toplevel_handle_v1::Event::WorkspaceEnter { workspace } => {
+                ToplevelHandleEvent::WorkspaceEnter {
+                    workspace: WorkspaceHandle::Zcosmic(workspace),
+                }
+            }
+            zcosmic_toplevel_handle_v1::Event::WorkspaceLeave { workspace } => {
+                ToplevelHandleEvent::WorkspaceLeave {
+                    workspace: WorkspaceHandle::Zcosmic(workspace),
+                }
+            }
             zcosmic_toplevel_handle_v1::Event::State { state } => {
                 let mut r_state = HashSet::new();
                 for value in state.chunks_exact(4) {
-                    if let Ok(state) = zcosmic_toplevel_handle_v1::State::try_from(
+                    if let Some(state) = zcosmic_toplevel_handle_v1::State::try_from(
                         u32::from_ne_bytes(value[0..4].try_into().unwrap()),
-                    ) {
-                        r_state.insert(ToplevelState::from(state));
+                    )
+                    .ok()
+                    .and_then(toplevel_state)
+                    {
+                        r_state.insert(state);
                     }
                 }
                 Self::State { state: r_state }
             }
-            _ => todo!(),
+            _ => Self::None,
+        }
+    }
+}
+
+/// States from later versions of the protocol are left out rather than guessed at
+fn workspace_state(value: zcosmic_workspace_handle_v1::State) -> Option<WorkspaceState> {
+    match value {
+        zcosmic_workspace_handle_v1::State::Active => Some(WorkspaceState::Active),
+        zcosmic_workspace_handle_v1::State::Urgent => Some(WorkspaceState::Urgent),
+        zcosmic_workspace_handle_v1::State::Hidden => Some(WorkspaceState::Hidden),
+        _ => None,
+    }
+}
+
+fn u32_array(array: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    array
+        .chunks_exact(4)
+        .map(|value| u32::from_ne_bytes(value[0..4].try_into().unwrap()))
+}
+
+impl From<zcosmic_workspace_handle_v1::Event> for WorkspaceHandleEvent {
+    fn from(value: zcosmic_workspace_handle_v1::Event) -> Self {
+        match value {
+            zcosmic_workspace_handle_v1::Event::Name { name } => Self::Name { name },
+            zcosmic_workspace_handle_v1::Event::Coordinates { coordinates } => Self::Coordinates {
+                coordinates: u32_array(&coordinates).collect(),
+            },
+            zcosmic_workspace_handle_v1::Event::State { state } => Self::State {
+                state: u32_array(&state)
+                    .filter_map(|value| zcosmic_workspace_handle_v1::State::try_from(value).ok())
+                    .filter_map(workspace_state)
+                    .collect(),
+            },
+            zcosmic_workspace_handle_v1::Event::Capabilities { capabilities } => {
+                Self::Capabilities {
+                    // zcosmic_workspace_capabilities_v1.remove
+                    can_remove: u32_array(&capabilities).any(|value| value == 3),
+                }
+            }
+            zcosmic_workspace_handle_v1::Event::Remove => Self::Remove,
+            _ => Self::None,
+        }
+    }
+}
+
+impl From<zcosmic_workspace_group_handle_v1::Event> for WorkspaceGroupEvent {
+    fn from(value: zcosmic_workspace_group_handle_v1::Event) -> Self {
+        match value {
+            zcosmic_workspace_group_handle_v1::Event::OutputEnter { output } => {
+                Self::OutputEnter { output }
+            }
+            zcosmic_workspace_group_handle_v1::Event::OutputLeave { output } => {
+                Self::OutputLeave { output }
+            }
+            zcosmic_workspace_group_handle_v1::Event::Capabilities { capabilities } => {
+                Self::Capabilities {
+                    // zcosmic_workspace_group_capabilities_v1.create_workspace
+                    can_create_workspace: u32_array(&capabilities).any(|value| value == 1),
+                }
+            }
+            zcosmic_workspace_group_handle_v1::Event::Workspace { workspace } => {
+                Self::WorkspaceEnter(WorkspaceHandle::Zcosmic(workspace))
+            }
+            zcosmic_workspace_group_handle_v1::Event::Remove => Self::Remove,
+            _ => Self::None,
+        }
+    }
+}
+
+impl From<zcosmic_workspace_manager_v1::Event> for WorkspaceManagerEvent {
+    fn from(value: zcosmic_workspace_manager_v1::Event) -> Self {
+        match value {
+            zcosmic_workspace_manager_v1::Event::WorkspaceGroup { workspace_group } => {
+                Self::WorkspaceGroup(WorkspaceGroupHandle::Zcosmic(workspace_group))
+            }
+            zcosmic_workspace_manager_v1::Event::Done => Self::Done,
+            _ => Self::Finished,
         }
     }
 }
@@ -62,7 +159,7 @@ impl From<zcosmic_toplevel_info_v1::Event> for ToplevelManagerEvent {
                 Self::Toplevel(ToplevelHandle::Zcosmic(toplevel))
             }
             zcosmic_toplevel_info_v1::Event::Finished => Self::Finished,
-            _ => todo!(),
+            _ => Self::None,
         }
     }
 }
@@ -79,10 +176,15 @@ impl Dispatch<zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1, ()> for AppDa
         _qhandle: &QueueHandle<Self>,
     ) {
         log::trace!("zcosmic_toplevel_handle_v1::event: {:?}", event);
-        state.handle_toplevel_handle_event(
-            ToplevelHandle::Zcosmic(toplevel.clone()),
-            ToplevelHandleEvent::from(event),
-        );
+        let handle = match state.cosmic_bridge.get(toplevel) {
+            // Bridged handles only carry extra state for the ext toplevel
+            Some(ext_handle) => match event {
+                zcosmic_toplevel_handle_v1::Event::Closed => return,
+                _ => ToplevelHandle::Ext(ext_handle.clone()),
+            },
+            None => ToplevelHandle::Zcosmic(toplevel.clone()),
+        };
+        state.handle_toplevel_handle_event(handle, ToplevelHandleEvent::from(event));
     }
 }
 
@@ -99,7 +201,7 @@ impl Dispatch<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, ()> for AppData {
         state.handle_toplevel_manager_event(ToplevelManagerEvent::from(event));
     }
 
-    wayland_client::event_created_child!(AppData, zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, [
+    event_created_child!(AppData, zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, [
         zcosmic_toplevel_info_v1::EVT_TOPLEVEL_OPCODE => (zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1, ())
     ]);
 }
@@ -116,3 +218,69 @@ impl Dispatch<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1, ()> for App
         // println!("Event! {:?}", event);
     }
 }
+
+// COSMIC Workspaces
+
+impl Dispatch<zcosmic_workspace_manager_v1::ZcosmicWorkspaceManagerV1, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        _proxy: &zcosmic_workspace_manager_v1::ZcosmicWorkspaceManagerV1,
+        event: <zcosmic_workspace_manager_v1::ZcosmicWorkspaceManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        log::trace!("zcosmic_workspace_manager_v1::event: {:?}", event);
+        state.handle_workspace_manager_event(WorkspaceManagerEvent::from(event));
+    }
+
+    event_created_child!(AppData, zcosmic_workspace_manager_v1::ZcosmicWorkspaceManagerV1, [
+        zcosmic_workspace_manager_v1::EVT_WORKSPACE_GROUP_OPCODE => (zcosmic_workspace_group_handle_v1::ZcosmicWorkspaceGroupHandleV1, ())
+    ]);
+}
+
+impl Dispatch<zcosmic_workspace_group_handle_v1::ZcosmicWorkspaceGroupHandleV1, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        group: &zcosmic_workspace_group_handle_v1::ZcosmicWorkspaceGroupHandleV1,
+        event: <zcosmic_workspace_group_handle_v1::ZcosmicWorkspaceGroupHandleV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        log::trace!("zcosmic_workspace_group_handle_v1::event: {:?}", event);
+        let removed = matches!(event, zcosmic_workspace_group_handle_v1::Event::Remove);
+        state.handle_workspace_group_event(
+            WorkspaceGroupHandle::Zcosmic(group.clone()),
+            WorkspaceGroupEvent::from(event),
+        );
+        if removed {
+            group.destroy();
+        }
+    }
+
+    event_created_child!(AppData, zcosmic_workspace_group_handle_v1::ZcosmicWorkspaceGroupHandleV1, [
+        zcosmic_workspace_group_handle_v1::EVT_WORKSPACE_OPCODE => (zcosmic_workspace_handle_v1::ZcosmicWorkspaceHandleV1, ())
+    ]);
+}
+
+impl Dispatch<zcosmic_workspace_handle_v1::ZcosmicWorkspaceHandleV1, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        workspace: &zcosmic_workspace_handle_v1::ZcosmicWorkspaceHandleV1,
+        event: <zcosmic_workspace_handle_v1::ZcosmicWorkspaceHandleV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        log::trace!("zcosmic_workspace_handle_v1::event: {:?}", event);
+        let removed = matches!(event, zcosmic_workspace_handle_v1::Event::Remove);
+        state.handle_workspace_handle_event(
+            WorkspaceHandle::Zcosmic(workspace.clone()),
+            WorkspaceHandleEvent::from(event),
+        );
+        if removed {
+            workspace.destroy();
+        }
+    }
+}
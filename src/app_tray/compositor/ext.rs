@@ -0,0 +1,266 @@
+use std::collections::HashSet;
+
+use wayland_client::{event_created_child, Connection, Dispatch, Proxy, QueueHandle};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::{
+    ext_foreign_toplevel_handle_v1, ext_foreign_toplevel_list_v1,
+};
+use workspace::v1::client::{
+    ext_workspace_group_handle_v1, ext_workspace_handle_v1, ext_workspace_manager_v1,
+};
+
+use super::{
+    workspace::{
+        WorkspaceGroupEvent, WorkspaceGroupHandle, WorkspaceHandle, WorkspaceHandleEvent,
+        WorkspaceManagerEvent, WorkspaceState,
+    },
+    AppData, ToplevelHandle, ToplevelHandleEvent, ToplevelManagerEvent,
+};
+
+/// ext-workspace-v1 isn't part of a wayland-protocols release we can use yet
+pub mod workspace {
+    pub mod v1 {
+        pub mod client {
+            use wayland_client;
+            use wayland_client::protocol::*;
+
+            pub mod __interfaces {
+                use wayland_client::protocol::__interfaces::*;
+                wayland_scanner::generate_interfaces!("protocols/ext-workspace-v1.xml");
+            }
+            use self::__interfaces::*;
+
+            wayland_scanner::generate_client_code!("protocols/ext-workspace-v1.xml");
+        }
+    }
+}
+
+impl From<ext_foreign_toplevel_handle_v1::Event> for ToplevelHandleEvent {
+    fn from(value: ext_foreign_toplevel_handle_v1::Event) -> Self {
+        match value {
+            ext_foreign_toplevel_handle_v1::Event::Closed => Self::Closed,
+            ext_foreign_toplevel_handle_v1::Event::Done => Self::Done,
+            ext_foreign_toplevel_handle_v1::Event::Title { title } => Self::Title { title },
+            ext_foreign_toplevel_handle_v1::Event::AppId { app_id } => Self::AppId { app_id },
+            ext_foreign_toplevel_handle_v1::Event::Identifier { identifier } => {
+                Self::Identifier { identifier }
+            }
+            _ => Self::None,
+        }
+    }
+}
+
+impl From<ext_foreign_toplevel_list_v1::Event> for ToplevelManagerEvent {
+    fn from(value: ext_foreign_toplevel_list_v1::Event) -> Self {
+        match value {
+            ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } => {
+                Self::Toplevel(ToplevelHandle::Ext(toplevel))
+            }
+            _ => Self::Finished,
+        }
+    }
+}
+
+impl From<ext_workspace_handle_v1::Event> for WorkspaceHandleEvent {
+    fn from(value: ext_workspace_handle_v1::Event) -> Self {
+        match value {
+            ext_workspace_handle_v1::Event::Id { id } => Self::Id { id },
+            ext_workspace_handle_v1::Event::Name { name } => Self::Name { name },
+            ext_workspace_handle_v1::Event::Coordinates { coordinates } => Self::Coordinates {
+                coordinates: coordinates
+                    .chunks_exact(4)
+                    .map(|value| u32::from_ne_bytes(value[0..4].try_into().unwrap()))
+                    .collect(),
+            },
+            ext_workspace_handle_v1::Event::State { state } => {
+                let mut r_state = HashSet::new();
+                if let Ok(state) = state.into_result() {
+                    if state.contains(ext_workspace_handle_v1::State::Active) {
+                        r_state.insert(WorkspaceState::Active);
+                    }
+                    if state.contains(ext_workspace_handle_v1::State::Urgent) {
+                        r_state.insert(WorkspaceState::Urgent);
+                    }
+                    if state.contains(ext_workspace_handle_v1::State::Hidden) {
+                        r_state.insert(WorkspaceState::Hidden);
+                    }
+                }
+                Self::State { state: r_state }
+            }
+            ext_workspace_handle_v1::Event::Capabilities { capabilities } => Self::Capabilities {
+                can_remove: capabilities.into_result().is_ok_and(|capabilities| {
+                    capabilities.contains(ext_workspace_handle_v1::WorkspaceCapabilities::Remove)
+                }),
+            },
+            ext_workspace_handle_v1::Event::Removed => Self::Remove,
+            _ => Self::None,
+        }
+    }
+}
+
+impl From<ext_workspace_group_handle_v1::Event> for WorkspaceGroupEvent {
+    fn from(value: ext_workspace_group_handle_v1::Event) -> Self {
+        match value {
+            ext_workspace_group_handle_v1::Event::Capabilities { capabilities } => {
+                Self::Capabilities {
+                    can_create_workspace: capabilities.into_result().is_ok_and(|capabilities| {
+                        capabilities.contains(
+                            ext_workspace_group_handle_v1::GroupCapabilities::CreateWorkspace,
+                        )
+                    }),
+                }
+            }
+            ext_workspace_group_handle_v1::Event::OutputEnter { output } => {
+                Self::OutputEnter { output }
+            }
+            ext_workspace_group_handle_v1::Event::OutputLeave { output } => {
+                Self::OutputLeave { output }
+            }
+            ext_workspace_group_handle_v1::Event::WorkspaceEnter { workspace } => {
+                Self::WorkspaceEnter(WorkspaceHandle::Ext(workspace))
+            }
+            ext_workspace_group_handle_v1::Event::WorkspaceLeave { workspace } => {
+                Self::WorkspaceLeave(WorkspaceHandle::Ext(workspace))
+            }
+            ext_workspace_group_handle_v1::Event::Removed => Self::Remove,
+            _ => Self::None,
+        }
+    }
+}
+
+impl From<ext_workspace_manager_v1::Event> for WorkspaceManagerEvent {
+    fn from(value: ext_workspace_manager_v1::Event) -> Self {
+        match value {
+            ext_workspace_manager_v1::Event::WorkspaceGroup { workspace_group } => {
+                Self::WorkspaceGroup(WorkspaceGroupHandle::Ext(workspace_group))
+            }
+            ext_workspace_manager_v1::Event::Workspace { workspace } => {
+                Self::Workspace(WorkspaceHandle::Ext(workspace))
+            }
+            ext_workspace_manager_v1::Event::Done => Self::Done,
+            _ => Self::Finished,
+        }
+    }
+}
+
+// EXT Foreign Toplevel List
+
+impl Dispatch<ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        toplevel: &ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1,
+        event: <ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        log::trace!("ext_foreign_toplevel_handle_v1::event: {:?}", event);
+        let closed = matches!(event, ext_foreign_toplevel_handle_v1::Event::Closed);
+        state.handle_toplevel_handle_event(
+            ToplevelHandle::Ext(toplevel.clone()),
+            ToplevelHandleEvent::from(event),
+        );
+        if closed {
+            state.cosmic_bridge.retain(|cosmic_handle, ext_handle| {
+                if ext_handle == toplevel {
+                    cosmic_handle.destroy();
+                    false
+                } else {
+                    true
+                }
+            });
+            toplevel.destroy();
+        }
+    }
+}
+
+impl Dispatch<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        _proxy: &ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1,
+        event: <ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        qhandle: &QueueHandle<Self>,
+    ) {
+        log::trace!("ext_foreign_toplevel_list_v1::event: {:?}", event);
+        if let ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } = &event {
+            // Bridge to cosmic's management protocol, which only understands its own handles
+            if let Some(info) = state
+                .zcosmic_toplevel_info
+                .as_ref()
+                .filter(|info| info.version() >= 2)
+            {
+                let cosmic_handle = info.get_cosmic_toplevel(toplevel, qhandle, ());
+                state.cosmic_bridge.insert(cosmic_handle, toplevel.clone());
+            }
+        }
+        state.handle_toplevel_manager_event(ToplevelManagerEvent::from(event));
+    }
+
+    event_created_child!(AppData, ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, [
+        ext_foreign_toplevel_list_v1::EVT_TOPLEVEL_OPCODE => (ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1, ())
+    ]);
+}
+
+// EXT Workspaces
+
+impl Dispatch<ext_workspace_manager_v1::ExtWorkspaceManagerV1, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        _proxy: &ext_workspace_manager_v1::ExtWorkspaceManagerV1,
+        event: <ext_workspace_manager_v1::ExtWorkspaceManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        log::trace!("ext_workspace_manager_v1::event: {:?}", event);
+        state.handle_workspace_manager_event(WorkspaceManagerEvent::from(event));
+    }
+
+    event_created_child!(AppData, ext_workspace_manager_v1::ExtWorkspaceManagerV1, [
+        ext_workspace_manager_v1::EVT_WORKSPACE_GROUP_OPCODE => (ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1, ()),
+        ext_workspace_manager_v1::EVT_WORKSPACE_OPCODE => (ext_workspace_handle_v1::ExtWorkspaceHandleV1, ())
+    ]);
+}
+
+impl Dispatch<ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        group: &ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1,
+        event: <ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        log::trace!("ext_workspace_group_handle_v1::event: {:?}", event);
+        let removed = matches!(event, ext_workspace_group_handle_v1::Event::Removed);
+        state.handle_workspace_group_event(
+            WorkspaceGroupHandle::Ext(group.clone()),
+            WorkspaceGroupEvent::from(event),
+        );
+        if removed {
+            group.destroy();
+        }
+    }
+}
+
+impl Dispatch<ext_workspace_handle_v1::ExtWorkspaceHandleV1, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        workspace: &ext_workspace_handle_v1::ExtWorkspaceHandleV1,
+        event: <ext_workspace_handle_v1::ExtWorkspaceHandleV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        log::trace!("ext_workspace_handle_v1::event: {:?}", event);
+        let removed = matches!(event, ext_workspace_handle_v1::Event::Removed);
+        state.handle_workspace_handle_event(
+            WorkspaceHandle::Ext(workspace.clone()),
+            WorkspaceHandleEvent::from(event),
+        );
+        if removed {
+            workspace.destroy();
+        }
+    }
+}
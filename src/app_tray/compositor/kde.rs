@@ -1,34 +1,229 @@
 // KDE Window Management
 
-use wayland_client::{Connection, Dispatch, Proxy, QueueHandle};
+use std::collections::HashSet;
+
+use wayland_client::{protocol::wl_output::WlOutput, Connection, Dispatch, Proxy, QueueHandle};
 use wayland_protocols_plasma::plasma_window_management::client::{
     org_kde_plasma_window, org_kde_plasma_window_management,
 };
 
-use super::AppData;
+use super::{
+    AppData, ToplevelHandle, ToplevelHandleEvent, ToplevelManagerEvent, ToplevelState,
+    ToplevelUpdate, WaylandIncoming,
+};
+
+pub const STATE_ACTIVE: u32 = org_kde_plasma_window_management::State::Active as u32;
+pub const STATE_MINIMIZED: u32 = org_kde_plasma_window_management::State::Minimized as u32;
+pub const STATE_MAXIMIZED: u32 = org_kde_plasma_window_management::State::Maximized as u32;
+pub const STATE_FULLSCREEN: u32 = org_kde_plasma_window_management::State::Fullscreen as u32;
+pub const STATE_SKIP_TASKBAR: u32 = org_kde_plasma_window_management::State::Skiptaskbar as u32;
+pub const STATE_DEMANDS_ATTENTION: u32 =
+    org_kde_plasma_window_management::State::DemandsAttention as u32;
+
+/// Per-window bookkeeping that the plasma protocol leaves to the client.
+#[derive(Clone, Debug)]
+pub struct KdeWindow {
+    pub uuid: String,
+    skip_taskbar: bool,
+    initialized: bool,
+}
+
+impl KdeWindow {
+    fn new(uuid: String) -> Self {
+        Self {
+            uuid,
+            skip_taskbar: false,
+            initialized: false,
+        }
+    }
+}
+
+fn state_from_flags(flags: u32) -> HashSet<ToplevelState> {
+    let mut state = HashSet::new();
+    if flags & STATE_ACTIVE != 0 {
+        state.insert(ToplevelState::Activated);
+    }
+    if flags & STATE_MINIMIZED != 0 {
+        state.insert(ToplevelState::Minimized);
+    }
+    if flags & STATE_MAXIMIZED != 0 {
+        state.insert(ToplevelState::Maximized);
+    }
+    if flags & STATE_FULLSCREEN != 0 {
+        state.insert(ToplevelState::Fullscreen);
+    }
+    // KWin also sets this when it denies an activation request from an unfocused window
+    if flags & STATE_DEMANDS_ATTENTION != 0 {
+        state.insert(ToplevelState::Urgent);
+    }
+    state
+}
+
+impl AppData {
+    fn kde_window_added(
+        &mut self,
+        manager: &org_kde_plasma_window_management::OrgKdePlasmaWindowManagement,
+        uuid: String,
+        qh: &QueueHandle<Self>,
+    ) {
+        if self.kde_windows.values().any(|window| window.uuid == uuid) {
+            return;
+        }
+        let window = manager.get_window_by_uuid(uuid.clone(), qh, ());
+        self.kde_windows
+            .insert(window.clone(), KdeWindow::new(uuid));
+        self.handle_toplevel_manager_event(ToplevelManagerEvent::Toplevel(ToplevelHandle::Kde(
+            window,
+        )));
+    }
+
+    /// Take a window that turned skip-taskbar back out of the tray. It stays tracked, so it's
+    /// announced again once the flag is cleared
+    fn kde_window_hidden(&mut self, handle: &ToplevelHandle) {
+        let Some((_, data)) = self
+            .toplevel_state
+            .toplevels
+            .iter_mut()
+            .find(|(toplevel, _)| toplevel == handle)
+        else {
+            return;
+        };
+        if data.current_info.take().is_some() {
+            let _ = self
+                .tx
+                .unbounded_send(WaylandIncoming::Toplevel(ToplevelUpdate::Remove(
+                    handle.clone(),
+                )));
+        }
+    }
+
+    /// Plasma windows report geometry instead of outputs, so figure out which outputs they cover
+    fn kde_window_outputs(&self, geometry: (i32, i32, i32, i32)) -> HashSet<WlOutput> {
+        let (x, y, w, h) = geometry;
+        self.output_state
+            .outputs()
+            .filter(|output| {
+                self.output_state.info(output).is_some_and(|info| {
+                    match (info.logical_position, info.logical_size) {
+                        (Some((ox, oy)), Some((ow, oh))) => {
+                            x < ox + ow && ox < x + w && y < oy + oh && oy < y + h
+                        }
+                        _ => false,
+                    }
+                })
+            })
+            .collect()
+    }
+}
 
 impl Dispatch<org_kde_plasma_window::OrgKdePlasmaWindow, ()> for AppData {
     fn event(
-        _state: &mut Self,
-        _proxy: &org_kde_plasma_window::OrgKdePlasmaWindow,
+        state: &mut Self,
+        proxy: &org_kde_plasma_window::OrgKdePlasmaWindow,
         event: <org_kde_plasma_window::OrgKdePlasmaWindow as Proxy>::Event,
         _data: &(),
         _conn: &Connection,
         _qhandle: &QueueHandle<Self>,
     ) {
-        println!("{:?}", event);
+        log::trace!("org_kde_plasma_window::event: {:?}", event);
+        let handle = ToplevelHandle::Kde(proxy.clone());
+        let Some(window) = state.kde_windows.get_mut(proxy) else {
+            // Events can still trickle in after the window was unmapped
+            return;
+        };
+        let events = match event {
+            org_kde_plasma_window::Event::TitleChanged { title } => {
+                vec![ToplevelHandleEvent::Title { title }]
+            }
+            org_kde_plasma_window::Event::AppIdChanged { app_id } => {
+                vec![ToplevelHandleEvent::AppId { app_id }]
+            }
+            org_kde_plasma_window::Event::StateChanged { flags } => {
+                window.skip_taskbar = flags & STATE_SKIP_TASKBAR != 0;
+                vec![ToplevelHandleEvent::State {
+                    state: state_from_flags(flags),
+                }]
+            }
+            org_kde_plasma_window::Event::Geometry {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let inside = state.kde_window_outputs((x, y, width as i32, height as i32));
+                state
+                    .output_state
+                    .outputs()
+                    .map(|output| {
+                        if inside.contains(&output) {
+                            ToplevelHandleEvent::OutputEnter { output }
+                        } else {
+                            ToplevelHandleEvent::OutputLeave { output }
+                        }
+                    })
+                    .collect()
+            }
+            org_kde_plasma_window::Event::InitialState => {
+                window.initialized = true;
+                Vec::new()
+            }
+            org_kde_plasma_window::Event::Unmapped => {
+                state.kde_windows.remove(proxy);
+                // Windows that never made it into the tray, or were taken out again, have
+                // nothing to close
+                let announced = state
+                    .toplevel_state
+                    .toplevels
+                    .iter()
+                    .any(|(toplevel, data)| *toplevel == handle && data.current_info.is_some());
+                if announced {
+                    state.handle_toplevel_handle_event(handle, ToplevelHandleEvent::Closed);
+                } else {
+                    state
+                        .toplevel_state
+                        .toplevels
+                        .retain(|(toplevel, _)| *toplevel != handle);
+                }
+                proxy.destroy();
+                return;
+            }
+            _ => Vec::new(),
+        };
+        for event in events {
+            state.handle_toplevel_handle_event(handle.clone(), event);
+        }
+        // Plasma has no done event, so commit every change once the initial state is in
+        match state.kde_windows.get(proxy) {
+            Some(window) if window.initialized && !window.skip_taskbar => {
+                state.handle_toplevel_handle_event(handle, ToplevelHandleEvent::Done);
+            }
+            Some(window) if window.initialized => state.kde_window_hidden(&handle),
+            _ => {}
+        }
     }
 }
 
 impl Dispatch<org_kde_plasma_window_management::OrgKdePlasmaWindowManagement, ()> for AppData {
     fn event(
-        _state: &mut Self,
-        _proxy: &org_kde_plasma_window_management::OrgKdePlasmaWindowManagement,
+        state: &mut Self,
+        proxy: &org_kde_plasma_window_management::OrgKdePlasmaWindowManagement,
         event: <org_kde_plasma_window_management::OrgKdePlasmaWindowManagement as Proxy>::Event,
         _data: &(),
         _conn: &Connection,
-        _qhandle: &QueueHandle<Self>,
+        qhandle: &QueueHandle<Self>,
     ) {
-        println!("{:?}", event);
+        log::trace!("org_kde_plasma_window_management::event: {:?}", event);
+        match event {
+            org_kde_plasma_window_management::Event::WindowWithUuid { uuid, .. } => {
+                state.kde_window_added(proxy, uuid, qhandle);
+            }
+            org_kde_plasma_window_management::Event::StackingOrderUuidChanged { uuids } => {
+                for uuid in uuids.split(';').filter(|uuid| !uuid.is_empty()) {
+                    state.kde_window_added(proxy, uuid.to_string(), qhandle);
+                }
+            }
+            // Deprecated id-based events, window_with_uuid covers these
+            _ => {}
+        }
     }
 }
@@ -1,11 +1,16 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Instant,
+};
 
 use cosmic_protocols::{
     toplevel_info::v1::client::{
         zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1, zcosmic_toplevel_info_v1,
     },
     toplevel_management::v1::client::zcosmic_toplevel_manager_v1,
-    workspace::v1::client::zcosmic_workspace_handle_v1,
+    workspace::v1::client::{
+        zcosmic_workspace_handle_v1::ZcosmicWorkspaceHandleV1, zcosmic_workspace_manager_v1,
+    },
 };
 use iced::{
     futures::{
@@ -14,11 +19,13 @@ use iced::{
         lock::Mutex,
         SinkExt, StreamExt,
     },
+    widget::image,
     Subscription, Task,
 };
 
 use once_cell::sync::Lazy;
 use smithay_client_toolkit::{
+    activation::ActivationState,
     output::{OutputHandler, OutputInfo, OutputState},
     reexports::{
         calloop::{
@@ -35,22 +42,38 @@ use wayland_client::{
         wl_output::{self, WlOutput},
         wl_registry::{self},
     },
-    Connection, QueueHandle,
+    Connection, Proxy, QueueHandle,
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::{
+    ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1, ext_foreign_toplevel_list_v1,
+};
+use wayland_protocols_plasma::plasma_window_management::client::{
+    org_kde_plasma_window::OrgKdePlasmaWindow, org_kde_plasma_window_management,
 };
-use wayland_protocols_plasma::plasma_window_management::client::org_kde_plasma_window_management;
 use wayland_protocols_wlr::foreign_toplevel::v1::client::{
     zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1, zwlr_foreign_toplevel_manager_v1,
 };
 
-use crate::app_tray::AppTrayMessage;
+use crate::{app_tray::AppTrayMessage, launcher::LaunchInfo};
+use ext::workspace::v1::client::ext_workspace_manager_v1;
 
+pub mod activation;
+pub mod capture;
 pub mod cosmic;
+pub mod ext;
 pub mod kde;
 pub mod wlr;
+pub mod workspace;
+
+pub use workspace::{
+    CompositorWorkspace, CompositorWorkspaceGroup, WorkspaceGroupHandle, WorkspaceHandle,
+    WorkspaceState,
+};
 
 struct AppData {
     exit: bool,
     tx: UnboundedSender<WaylandIncoming>,
+    queue_handle: QueueHandle<AppData>,
     output_state: OutputState,
     seat_state: SeatState,
     toplevel_state: ToplevelManager,
@@ -58,6 +81,14 @@ struct AppData {
     zcosmic_toplevel_info: Option<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1>,
     zcosmic_toplevel_manager: Option<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1>,
     kde_window_manager: Option<org_kde_plasma_window_management::OrgKdePlasmaWindowManagement>,
+    kde_windows: HashMap<OrgKdePlasmaWindow, kde::KdeWindow>,
+    ext_toplevel_list: Option<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1>,
+    cosmic_bridge: HashMap<ZcosmicToplevelHandleV1, ExtForeignToplevelHandleV1>,
+    workspace_state: workspace::WorkspaceManager,
+    zcosmic_workspace_manager: Option<zcosmic_workspace_manager_v1::ZcosmicWorkspaceManagerV1>,
+    ext_workspace_manager: Option<ext_workspace_manager_v1::ExtWorkspaceManagerV1>,
+    activation_state: Option<ActivationState>,
+    capture_state: Option<capture::CaptureState>,
 }
 
 impl AppData {
@@ -76,12 +107,21 @@ impl AppData {
             ToplevelHandleEvent::AppId { app_id } => {
                 data.pending_info.app_id = app_id;
             }
+            ToplevelHandleEvent::Identifier { identifier } => {
+                data.pending_info.identifier = Some(identifier);
+            }
             ToplevelHandleEvent::OutputEnter { output } => {
                 data.pending_info.output.insert(output);
             }
             ToplevelHandleEvent::OutputLeave { output } => {
                 data.pending_info.output.remove(&output);
             }
+            ToplevelHandleEvent::WorkspaceEnter { workspace } => {
+                data.pending_info.workspace.insert(workspace);
+            }
+            ToplevelHandleEvent::WorkspaceLeave { workspace } => {
+                data.pending_info.workspace.remove(&workspace);
+            }
             ToplevelHandleEvent::State { state } => {
                 log::debug!(
                     "{} STATE CHANGE! new_pending: {:?} -> pending: {:?} :: current: {:?}",
@@ -111,6 +151,7 @@ impl AppData {
                                 data.current_info.as_ref().unwrap().clone(),
                             )));
                 }
+                self.drop_mismatched_sessions();
             }
             ToplevelHandleEvent::Closed => {
                 let _ = self
@@ -123,6 +164,7 @@ impl AppData {
                 if let Some(idx) = toplevels.iter().position(|(handle, _)| *handle == toplevel) {
                     toplevels.remove(idx);
                 }
+                self.drop_mismatched_sessions();
             }
             ToplevelHandleEvent::None => {}
         }
@@ -135,7 +177,193 @@ impl AppData {
                     .toplevels
                     .push((toplevel, ToplevelData::default()));
             }
-            ToplevelManagerEvent::Finished => {}
+            ToplevelManagerEvent::Finished | ToplevelManagerEvent::None => {}
+        }
+    }
+
+    fn handle_toplevel_request(&mut self, req: WaylandToplevelRequest) {
+        match req {
+            WaylandToplevelRequest::Activate(handle) => match self.management_handle(handle) {
+                Some(ToplevelHandle::Zwlr(handle)) => {
+                    log::debug!("Activating toplevel!");
+                    if let Some(seat) = self.seat_state.seats().next() {
+                        // wlr has no implicit unminimize on activate
+                        handle.unset_minimized();
+                        handle.activate(&seat);
+                    }
+                }
+                Some(ToplevelHandle::Zcosmic(handle)) => {
+                    log::debug!("Activating toplevel!");
+                    if let Some(seat) = self.seat_state.seats().next() {
+                        let manager = self.zcosmic_toplevel_manager.as_ref().unwrap();
+                        manager.activate(&handle, &seat);
+                    }
+                }
+                Some(ToplevelHandle::Kde(handle)) => {
+                    log::debug!("Activating toplevel!");
+                    handle.set_state(kde::STATE_ACTIVE | kde::STATE_MINIMIZED, kde::STATE_ACTIVE);
+                }
+                Some(ToplevelHandle::Ext(_)) | None => {}
+            },
+            WaylandToplevelRequest::Minimize(handle) => match self.management_handle(handle) {
+                Some(ToplevelHandle::Zwlr(handle)) => {
+                    log::debug!("Minimizing toplevel!");
+                    handle.set_minimized();
+                }
+                Some(ToplevelHandle::Zcosmic(handle)) => {
+                    log::debug!("Minimizing toplevel!");
+                    let manager = self.zcosmic_toplevel_manager.as_ref().unwrap();
+                    manager.set_minimized(&handle);
+                }
+                Some(ToplevelHandle::Kde(handle)) => {
+                    log::debug!("Minimizing toplevel!");
+                    handle.set_state(kde::STATE_MINIMIZED, kde::STATE_MINIMIZED);
+                }
+                Some(ToplevelHandle::Ext(_)) | None => {}
+            },
+            WaylandToplevelRequest::Quit(handle) => match self.management_handle(handle) {
+                Some(ToplevelHandle::Zwlr(handle)) => {
+                    handle.close();
+                }
+                Some(ToplevelHandle::Zcosmic(handle)) => {
+                    let manager = self.zcosmic_toplevel_manager.as_ref().unwrap();
+                    manager.close(&handle);
+                }
+                Some(ToplevelHandle::Kde(handle)) => {
+                    handle.close();
+                }
+                Some(ToplevelHandle::Ext(_)) | None => {}
+            },
+            WaylandToplevelRequest::SetMaximized(handle, maximized) => {
+                match self.management_handle(handle) {
+                    Some(ToplevelHandle::Zwlr(handle)) => {
+                        if maximized {
+                            handle.set_maximized();
+                        } else {
+                            handle.unset_maximized();
+                        }
+                    }
+                    Some(ToplevelHandle::Zcosmic(handle)) => {
+                        let manager = self.zcosmic_toplevel_manager.as_ref().unwrap();
+                        if maximized {
+                            manager.set_maximized(&handle);
+                        } else {
+                            manager.unset_maximized(&handle);
+                        }
+                    }
+                    Some(ToplevelHandle::Kde(handle)) => {
+                        handle.set_state(
+                            kde::STATE_MAXIMIZED,
+                            if maximized { kde::STATE_MAXIMIZED } else { 0 },
+                        );
+                    }
+                    Some(ToplevelHandle::Ext(_)) | None => {}
+                }
+            }
+            WaylandToplevelRequest::SetFullscreen(handle, fullscreen) => {
+                match self.management_handle(handle) {
+                    Some(ToplevelHandle::Zwlr(handle)) if handle.version() >= 2 => {
+                        if fullscreen {
+                            handle.set_fullscreen(None);
+                        } else {
+                            handle.unset_fullscreen();
+                        }
+                    }
+                    Some(ToplevelHandle::Zcosmic(handle)) => {
+                        let manager = self.zcosmic_toplevel_manager.as_ref().unwrap();
+                        if fullscreen {
+                            manager.set_fullscreen(&handle, None);
+                        } else {
+                            manager.unset_fullscreen(&handle);
+                        }
+                    }
+                    Some(ToplevelHandle::Kde(handle)) => {
+                        handle.set_state(
+                            kde::STATE_FULLSCREEN,
+                            if fullscreen { kde::STATE_FULLSCREEN } else { 0 },
+                        );
+                    }
+                    Some(ToplevelHandle::Zwlr(_)) => {
+                        log::warn!("zwlr_foreign_toplevel_handle_v1 v1 can't set fullscreen");
+                    }
+                    Some(ToplevelHandle::Ext(_)) | None => {}
+                }
+            }
+            WaylandToplevelRequest::MoveToWorkspace(handle, workspace) => {
+                match (self.management_handle(handle), workspace) {
+                    (
+                        Some(ToplevelHandle::Zcosmic(handle)),
+                        WorkspaceHandle::Zcosmic(workspace),
+                    ) => {
+                        let Some(output) = self
+                            .workspace_state
+                            .output_of(&WorkspaceHandle::Zcosmic(workspace.clone()))
+                        else {
+                            log::warn!("Workspace has no output to move the toplevel to");
+                            return;
+                        };
+                        self.cosmic_move_to_workspace(&handle, &workspace, &output);
+                    }
+                    (Some(handle), _) => {
+                        log::warn!("Moving {:?} to a workspace is not supported", handle);
+                    }
+                    (None, _) => {}
+                }
+            }
+            WaylandToplevelRequest::MoveToOutput(handle, output) => {
+                match self.management_handle(handle) {
+                    Some(ToplevelHandle::Zcosmic(handle)) => {
+                        // cosmic moves toplevels between outputs through their workspaces
+                        let workspace = self.workspace_state.active_workspace_on(&output);
+                        match workspace {
+                            Some(WorkspaceHandle::Zcosmic(workspace)) => {
+                                self.cosmic_move_to_workspace(&handle, &workspace, &output);
+                            }
+                            _ => log::warn!("No active workspace on the output to move to"),
+                        }
+                    }
+                    Some(ToplevelHandle::Kde(handle)) if handle.version() >= 15 => {
+                        handle.send_to_output(&output);
+                    }
+                    Some(handle) => {
+                        log::warn!("Moving {:?} to an output is not supported", handle);
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+
+    fn cosmic_move_to_workspace(
+        &self,
+        handle: &ZcosmicToplevelHandleV1,
+        workspace: &ZcosmicWorkspaceHandleV1,
+        output: &WlOutput,
+    ) {
+        let manager = self.zcosmic_toplevel_manager.as_ref().unwrap();
+        if manager.version() >= 2 {
+            manager.move_to_workspace(handle, workspace, output);
+        } else {
+            log::warn!("zcosmic_toplevel_manager_v1 is too old to move toplevels");
+        }
+    }
+
+    /// ext-foreign-toplevel-list handles are read-only, so requests have to go through
+    /// whichever management protocol the handle was bridged to.
+    fn management_handle(&self, handle: ToplevelHandle) -> Option<ToplevelHandle> {
+        match handle {
+            ToplevelHandle::Ext(ext_handle) => {
+                let bridged = self
+                    .cosmic_bridge
+                    .iter()
+                    .find(|(_, bridged)| **bridged == ext_handle)
+                    .map(|(cosmic_handle, _)| ToplevelHandle::Zcosmic(cosmic_handle.clone()));
+                if bridged.is_none() {
+                    log::warn!("No management protocol available for {:?}", ext_handle);
+                }
+                bridged
+            }
+            handle => Some(handle),
         }
     }
 }
@@ -160,17 +388,15 @@ pub struct ToplevelInfo {
     pub workspace: HashSet<WorkspaceHandle>,
 }
 
-#[derive(Clone, Debug)]
-pub enum WorkspaceHandle {
-    Zcosmic(zcosmic_workspace_handle_v1::ZcosmicWorkspaceHandleV1),
-}
-
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ToplevelState {
     Maximized,
     Minimized,
     Fullscreen,
     Activated,
+    /// Demands attention. Only Plasma says so, activation requests between other clients can't
+    /// be seen by the panel
+    Urgent,
 }
 
 impl SeatHandler for AppData {
@@ -268,8 +494,11 @@ impl OutputHandler for AppData {
 enum ToplevelHandleEvent {
     Title { title: String },
     AppId { app_id: String },
+    Identifier { identifier: String },
     OutputEnter { output: WlOutput },
     OutputLeave { output: WlOutput },
+    WorkspaceEnter { workspace: WorkspaceHandle },
+    WorkspaceLeave { workspace: WorkspaceHandle },
     State { state: HashSet<ToplevelState> },
     Done,
     Closed,
@@ -280,6 +509,7 @@ enum ToplevelHandleEvent {
 enum ToplevelManagerEvent {
     Toplevel(ToplevelHandle),
     Finished,
+    None,
 }
 
 // WL REGISTRY
@@ -324,61 +554,10 @@ fn wayland_client_listener(tx: UnboundedSender<WaylandIncoming>, rx: Channel<Way
             channel::Event::Msg(req) => {
                 log::trace!("WaylandRequest: {:?}", req);
                 match req {
-                    WaylandRequest::Toplevel(req) => match req {
-                        WaylandToplevelRequest::Activate(handle) => match handle {
-                            ToplevelHandle::Zwlr(zwlr_foreign_toplevel_handle_v1) => todo!(),
-                            ToplevelHandle::Zcosmic(handle) => {
-                                log::debug!("Activating toplevel!");
-                                if let Some(seat) = state.seat_state.seats().next() {
-                                    let manager = state.zcosmic_toplevel_manager.as_ref().unwrap();
-                                    manager.activate(&handle, &seat);
-                                }
-                            }
-                        },
-                        WaylandToplevelRequest::Minimize(handle) => match handle {
-                            ToplevelHandle::Zwlr(zwlr_foreign_toplevel_handle_v1) => todo!(),
-                            ToplevelHandle::Zcosmic(handle) => {
-                                log::debug!("Minimizing toplevel!");
-                                let manager = state.zcosmic_toplevel_manager.as_ref().unwrap();
-                                manager.set_minimized(&handle);
-                            }
-                        },
-                        WaylandToplevelRequest::Quit(handle) => match handle {
-                            ToplevelHandle::Zwlr(zwlr_foreign_toplevel_handle_v1) => todo!(),
-                            ToplevelHandle::Zcosmic(handle) => {
-                                let manager = state.zcosmic_toplevel_manager.as_ref().unwrap();
-                                manager.close(&handle);
-                            }
-                        },
-                    },
-                    WaylandRequest::TokenRequest {
-                        app_id: _,
-                        exec: _,
-                        gpu_idx: _,
-                    } => {
-                        // if let Some(activation_state) = state.activation_state.as_ref() {
-                        //     let seat_and_serial = state.seat_state.seats().next().map(|seat| (seat, 0));
-                        //     activation_state.request_token_with_data(
-                        //         &state.queue_handle,
-                        //         ExecRequestData {
-                        //             data: RequestData {
-                        //                 app_id: Some(app_id),
-                        //                 seat_and_serial,
-                        //                 surface: None,
-                        //             },
-                        //             exec,
-                        //             gpu_idx,
-                        //         },
-                        //     );
-                        // } else {
-                        //     // let _ = state.tx.unbounded_send(WaylandIncoming::ActivationToken {
-                        //     //     _token: None,
-                        //     //     _app_id: Some(app_id),
-                        //     //     _exec: exec,
-                        //     //     _gpu_idx: gpu_idx,
-                        //     // });
-                        // }
-                    }
+                    WaylandRequest::Toplevel(req) => state.handle_toplevel_request(req),
+                    WaylandRequest::Workspace(req) => state.handle_workspace_request(req),
+                    WaylandRequest::TokenRequest(info) => state.handle_token_request(info),
+                    WaylandRequest::Capture(handles) => state.handle_capture_request(handles),
                 }
             }
             channel::Event::Closed => {
@@ -400,7 +579,7 @@ fn wayland_client_listener(tx: UnboundedSender<WaylandIncoming>, rx: Channel<Way
     let zwlr_toplevel_manager = match globals
         .bind::<zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1, _, _>(
         &qh,
-        3..=3,
+        1..=3,
         (),
     ) {
         Ok(manager) => Some(manager),
@@ -414,7 +593,7 @@ fn wayland_client_listener(tx: UnboundedSender<WaylandIncoming>, rx: Channel<Way
     };
 
     let zcosmic_toplevel_info = match globals
-        .bind::<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, _, _>(&qh, 1..=1, ())
+        .bind::<zcosmic_toplevel_info_v1::ZcosmicToplevelInfoV1, _, _>(&qh, 1..=2, ())
     {
         Ok(manager) => Some(manager),
         Err(e) => {
@@ -429,7 +608,7 @@ fn wayland_client_listener(tx: UnboundedSender<WaylandIncoming>, rx: Channel<Way
     let zcosmic_toplevel_manager = match globals
         .bind::<zcosmic_toplevel_manager_v1::ZcosmicToplevelManagerV1, _, _>(
         &qh,
-        1..=1,
+        1..=2,
         (),
     ) {
         Ok(manager) => Some(manager),
@@ -442,10 +621,12 @@ fn wayland_client_listener(tx: UnboundedSender<WaylandIncoming>, rx: Channel<Way
         }
     };
 
+    // Windows are only tracked by uuid, which window_with_uuid announces from version 13 on.
+    // Moving them to another output needs 15, see ToplevelHandle::can_move_to_output
     let kde_window_manager = match globals
         .bind::<org_kde_plasma_window_management::OrgKdePlasmaWindowManagement, _, _>(
         &qh,
-        15..=16,
+        13..=16,
         (),
     ) {
         Ok(manager) => Some(manager),
@@ -458,6 +639,76 @@ fn wayland_client_listener(tx: UnboundedSender<WaylandIncoming>, rx: Channel<Way
         }
     };
 
+    let zcosmic_workspace_manager = match globals
+        .bind::<zcosmic_workspace_manager_v1::ZcosmicWorkspaceManagerV1, _, _>(
+        &qh,
+        1..=1,
+        (),
+    ) {
+        Ok(manager) => Some(manager),
+        Err(e) => {
+            log::info!(
+                "[PROTOCOL] zcosmic_workspace_manager_v1 could not be bound: {}",
+                e
+            );
+            None
+        }
+    };
+
+    let ext_workspace_manager = match globals
+        .bind::<ext_workspace_manager_v1::ExtWorkspaceManagerV1, _, _>(&qh, 1..=1, ())
+    {
+        Ok(manager) => Some(manager),
+        Err(e) => {
+            log::info!(
+                "[PROTOCOL] ext_workspace_manager_v1 could not be bound: {}",
+                e
+            );
+            None
+        }
+    };
+
+    // The ext list carries no state and can't be acted on by itself, so only use it when
+    // it can be bridged to cosmic, or as a read-only fallback when nothing else is around
+    let ext_toplevel_list = if zcosmic_toplevel_info
+        .as_ref()
+        .is_some_and(|info| info.version() >= 2)
+        || (zwlr_toplevel_manager.is_none()
+            && zcosmic_toplevel_info.is_none()
+            && kde_window_manager.is_none())
+    {
+        match globals.bind::<ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1, _, _>(
+            &qh,
+            1..=1,
+            (),
+        ) {
+            Ok(list) => Some(list),
+            Err(e) => {
+                log::info!(
+                    "[PROTOCOL] ext_foreign_toplevel_list_v1 could not be bound: {}",
+                    e
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let activation_state = match ActivationState::bind(&globals, &qh) {
+        Ok(state) => Some(state),
+        Err(e) => {
+            log::info!("[PROTOCOL] xdg_activation_v1 could not be bound: {}", e);
+            None
+        }
+    };
+
+    // Capture sources are made from ext toplevels, when windows come from another protocol
+    // the ext list is bound just for capturing
+    let capture_state =
+        capture::CaptureState::bind(&globals, &qh, handle.clone(), ext_toplevel_list.is_none());
+    let _ = tx.unbounded_send(WaylandIncoming::CaptureAvailable(capture_state.is_some()));
+
     // let zwlr_toplevel_handle: zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1 =
     //     globals.bind(&qh, 3..=3, ()).unwrap();
 
@@ -467,6 +718,7 @@ fn wayland_client_listener(tx: UnboundedSender<WaylandIncoming>, rx: Channel<Way
     let mut app_data = AppData {
         exit: false,
         tx,
+        queue_handle: qh.clone(),
         output_state: OutputState::new(&globals, &qh),
         seat_state: SeatState::new(&globals, &qh),
         toplevel_state: ToplevelManager::default(),
@@ -474,6 +726,14 @@ fn wayland_client_listener(tx: UnboundedSender<WaylandIncoming>, rx: Channel<Way
         zcosmic_toplevel_manager,
         zwlr_toplevel_manager,
         kde_window_manager,
+        kde_windows: HashMap::new(),
+        ext_toplevel_list,
+        cosmic_bridge: HashMap::new(),
+        workspace_state: workspace::WorkspaceManager::default(),
+        zcosmic_workspace_manager,
+        ext_workspace_manager,
+        activation_state,
+        capture_state,
     };
 
     loop {
@@ -487,6 +747,7 @@ fn wayland_client_listener(tx: UnboundedSender<WaylandIncoming>, rx: Channel<Way
 
 smithay_client_toolkit::delegate_seat!(AppData);
 smithay_client_toolkit::delegate_output!(AppData);
+smithay_client_toolkit::delegate_activation!(AppData, activation::ExecRequestData);
 
 #[derive(Clone, Debug)]
 pub enum WaylandIncoming {
@@ -494,6 +755,19 @@ pub enum WaylandIncoming {
     Finished,
     Toplevel(ToplevelUpdate),
     Output(OutputUpdate),
+    Workspaces(Vec<CompositorWorkspaceGroup>),
+    LaunchToken {
+        app_id: String,
+        token: String,
+    },
+    LaunchFailed {
+        app_id: String,
+        token: Option<String>,
+        error: String,
+    },
+    /// Whether window thumbnails can be captured at all
+    CaptureAvailable(bool),
+    Thumbnail(ToplevelHandle, capture::Thumbnail),
 }
 
 #[derive(Clone, Debug)]
@@ -507,8 +781,11 @@ pub enum ToplevelUpdate {
 pub struct CompositorToplevelInfo {
     pub title: String,
     pub app_id: String,
+    /// Stable identifier, only provided by ext-foreign-toplevel-list
+    pub identifier: Option<String>,
     pub state: HashSet<ToplevelState>,
     pub output: HashSet<wl_output::WlOutput>,
+    pub workspace: HashSet<WorkspaceHandle>,
 }
 
 #[derive(Clone, Debug)]
@@ -520,10 +797,20 @@ pub enum OutputUpdate {
 
 #[derive(Clone, Debug)]
 pub enum WaylandOutgoing {
-    Exec(String, String),
+    Exec(LaunchInfo),
     Toggle(ToplevelHandle),
-    #[allow(unused)]
     Activate(ToplevelHandle),
+    Minimize(ToplevelHandle),
+    Close(ToplevelHandle),
+    SetMaximized(ToplevelHandle, bool),
+    SetFullscreen(ToplevelHandle, bool),
+    MoveToWorkspace(ToplevelHandle, WorkspaceHandle),
+    MoveToOutput(ToplevelHandle, WlOutput),
+    ActivateWorkspace(WorkspaceHandle),
+    CreateWorkspace(WorkspaceGroupHandle, String),
+    RemoveWorkspace(WorkspaceHandle),
+    /// Keep thumbnails of these windows coming, an empty list stops capturing
+    Capture(Vec<ToplevelHandle>),
 }
 
 #[derive(Debug, Clone)]
@@ -537,25 +824,57 @@ pub enum ToplevelRequest {
 #[derive(Clone, Debug)]
 pub enum WaylandRequest {
     Toplevel(WaylandToplevelRequest),
-    TokenRequest {
-        app_id: String,
-        exec: String,
-        gpu_idx: Option<usize>,
-    },
+    Workspace(WaylandWorkspaceRequest),
+    TokenRequest(LaunchInfo),
+    Capture(Vec<ToplevelHandle>),
 }
 
 #[derive(Debug, Clone)]
 pub enum WaylandToplevelRequest {
     Activate(ToplevelHandle),
     Minimize(ToplevelHandle),
-    #[allow(unused)]
     Quit(ToplevelHandle),
+    SetMaximized(ToplevelHandle, bool),
+    SetFullscreen(ToplevelHandle, bool),
+    MoveToWorkspace(ToplevelHandle, WorkspaceHandle),
+    MoveToOutput(ToplevelHandle, WlOutput),
+}
+
+#[derive(Debug, Clone)]
+pub enum WaylandWorkspaceRequest {
+    Activate(WorkspaceHandle),
+    Create {
+        group: WorkspaceGroupHandle,
+        name: String,
+    },
+    Remove(WorkspaceHandle),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ToplevelHandle {
     Zwlr(ZwlrForeignToplevelHandleV1),
     Zcosmic(ZcosmicToplevelHandleV1),
+    Kde(OrgKdePlasmaWindow),
+    Ext(ExtForeignToplevelHandleV1),
+}
+
+impl ToplevelHandle {
+    /// wlr handles only take fullscreen requests from version 2 on
+    pub fn can_fullscreen(&self) -> bool {
+        match self {
+            ToplevelHandle::Zwlr(handle) => handle.version() >= 2,
+            _ => true,
+        }
+    }
+
+    /// Plasma windows only take send_to_output from version 15 on
+    pub fn can_move_to_output(&self) -> bool {
+        match self {
+            ToplevelHandle::Kde(handle) => handle.version() >= 15,
+            ToplevelHandle::Zwlr(_) => false,
+            _ => true,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -563,8 +882,19 @@ pub struct CompositorBackend {
     wayland_sender: Option<Sender<WaylandRequest>>,
     // active_workspaces: Vec<ZcosmicWorkspaceHandleV1>,
     pub active_toplevels: HashMap<String, HashMap<ToplevelHandle, CompositorToplevelInfo>>,
+    pub workspace_groups: Vec<CompositorWorkspaceGroup>,
     output_list: HashMap<WlOutput, OutputInfo>,
     _current_output: String, // TODO: Get current output
+    /// Toplevels in the order they appeared, `active_toplevels` has no order of its own
+    toplevel_order: Vec<ToplevelHandle>,
+    /// Increases every time a different toplevel gets focus
+    focus_serial: u64,
+    last_focused: HashMap<ToplevelHandle, u64>,
+    /// Toplevels that want attention and since when
+    urgent_since: HashMap<ToplevelHandle, Instant>,
+    capture_available: bool,
+    /// Latest captured thumbnail of each window
+    thumbnails: HashMap<ToplevelHandle, image::Handle>,
 }
 
 pub enum State {
@@ -620,8 +950,15 @@ impl CompositorBackend {
         Self {
             wayland_sender: None,
             active_toplevels: HashMap::new(),
+            workspace_groups: Vec::new(),
             output_list: HashMap::new(),
             _current_output: "".to_string(),
+            toplevel_order: Vec::new(),
+            focus_serial: 0,
+            last_focused: HashMap::new(),
+            urgent_since: HashMap::new(),
+            capture_available: false,
+            thumbnails: HashMap::new(),
         }
     }
 
@@ -639,13 +976,9 @@ impl CompositorBackend {
 
     pub fn handle_outgoing(&mut self, outgoing: WaylandOutgoing) -> Option<Task<AppTrayMessage>> {
         match outgoing {
-            WaylandOutgoing::Exec(app_id, exec) => {
+            WaylandOutgoing::Exec(info) => {
                 if let Some(tx) = self.wayland_sender.as_ref() {
-                    let _ = tx.send(WaylandRequest::TokenRequest {
-                        app_id,
-                        exec,
-                        gpu_idx: None,
-                    });
+                    let _ = tx.send(WaylandRequest::TokenRequest(info));
                 }
                 None
             }
@@ -678,6 +1011,85 @@ impl CompositorBackend {
                 // }
                 None
             }
+            WaylandOutgoing::Minimize(window) => {
+                if let Some(tx) = self.wayland_sender.as_ref() {
+                    let _ = tx.send(WaylandRequest::Toplevel(WaylandToplevelRequest::Minimize(
+                        window,
+                    )));
+                }
+                None
+            }
+            WaylandOutgoing::Close(window) => {
+                if let Some(tx) = self.wayland_sender.as_ref() {
+                    let _ = tx.send(WaylandRequest::Toplevel(WaylandToplevelRequest::Quit(
+                        window,
+                    )));
+                }
+                None
+            }
+            WaylandOutgoing::SetMaximized(window, maximized) => {
+                if let Some(tx) = self.wayland_sender.as_ref() {
+                    let _ = tx.send(WaylandRequest::Toplevel(
+                        WaylandToplevelRequest::SetMaximized(window, maximized),
+                    ));
+                }
+                None
+            }
+            WaylandOutgoing::SetFullscreen(window, fullscreen) => {
+                if let Some(tx) = self.wayland_sender.as_ref() {
+                    let _ = tx.send(WaylandRequest::Toplevel(
+                        WaylandToplevelRequest::SetFullscreen(window, fullscreen),
+                    ));
+                }
+                None
+            }
+            WaylandOutgoing::MoveToWorkspace(window, workspace) => {
+                if let Some(tx) = self.wayland_sender.as_ref() {
+                    let _ = tx.send(WaylandRequest::Toplevel(
+                        WaylandToplevelRequest::MoveToWorkspace(window, workspace),
+                    ));
+                }
+                None
+            }
+            WaylandOutgoing::MoveToOutput(window, output) => {
+                if let Some(tx) = self.wayland_sender.as_ref() {
+                    let _ = tx.send(WaylandRequest::Toplevel(
+                        WaylandToplevelRequest::MoveToOutput(window, output),
+                    ));
+                }
+                None
+            }
+            WaylandOutgoing::ActivateWorkspace(workspace) => {
+                if let Some(tx) = self.wayland_sender.as_ref() {
+                    let _ = tx.send(WaylandRequest::Workspace(
+                        WaylandWorkspaceRequest::Activate(workspace),
+                    ));
+                }
+                None
+            }
+            WaylandOutgoing::CreateWorkspace(group, name) => {
+                if let Some(tx) = self.wayland_sender.as_ref() {
+                    let _ = tx.send(WaylandRequest::Workspace(WaylandWorkspaceRequest::Create {
+                        group,
+                        name,
+                    }));
+                }
+                None
+            }
+            WaylandOutgoing::RemoveWorkspace(workspace) => {
+                if let Some(tx) = self.wayland_sender.as_ref() {
+                    let _ = tx.send(WaylandRequest::Workspace(WaylandWorkspaceRequest::Remove(
+                        workspace,
+                    )));
+                }
+                None
+            }
+            WaylandOutgoing::Capture(handles) => {
+                if let Some(tx) = self.wayland_sender.as_ref() {
+                    let _ = tx.send(WaylandRequest::Capture(handles));
+                }
+                None
+            }
         }
     }
 
@@ -690,6 +1102,11 @@ impl CompositorBackend {
             WaylandIncoming::Finished => None,
             WaylandIncoming::Toplevel(toplevel_update) => match toplevel_update {
                 ToplevelUpdate::Add(handle, info) => {
+                    if !self.toplevel_order.contains(&handle) {
+                        self.toplevel_order.push(handle.clone());
+                    }
+                    self.track_focus(&handle, &info);
+                    self.track_urgency(&handle, &info);
                     let app_id = info.app_id.clone();
                     if self.active_toplevels.contains_key(&app_id) {
                         self.active_toplevels
@@ -709,6 +1126,8 @@ impl CompositorBackend {
                     } else if !self.active_toplevels.contains_key(&info.app_id) {
                         return Some(Task::none());
                     }
+                    self.track_focus(&handle, &info);
+                    self.track_urgency(&handle, &info);
 
                     for (t_handle, t_info) in self.active_toplevels.get_mut(&info.app_id).unwrap() {
                         if &handle == t_handle {
@@ -720,6 +1139,10 @@ impl CompositorBackend {
                     None
                 }
                 ToplevelUpdate::Remove(handle) => {
+                    self.toplevel_order.retain(|x| *x != handle);
+                    self.last_focused.remove(&handle);
+                    self.urgent_since.remove(&handle);
+                    self.thumbnails.remove(&handle);
                     let mut target_app_id: Option<String> = None;
                     for (app_id, app_info) in self.active_toplevels.iter_mut() {
                         if app_info.contains_key(&handle.clone()) {
@@ -750,27 +1173,56 @@ impl CompositorBackend {
                     None
                 }
             },
+            WaylandIncoming::Workspaces(groups) => {
+                self.workspace_groups = groups;
+                None
+            }
+            WaylandIncoming::LaunchToken { app_id, token } => {
+                log::debug!("Got activation token {} for {}", token, app_id);
+                None
+            }
+            WaylandIncoming::LaunchFailed { app_id, error, .. } => {
+                log::error!("Failed to launch {}: {}", app_id, error);
+                None
+            }
+            WaylandIncoming::CaptureAvailable(available) => {
+                self.capture_available = available;
+                None
+            }
+            WaylandIncoming::Thumbnail(handle, thumbnail) => {
+                // Late frames can still arrive for a window that just closed
+                if self.toplevel_order.contains(&handle) {
+                    self.thumbnails.insert(
+                        handle,
+                        image::Handle::from_rgba(
+                            thumbnail.width,
+                            thumbnail.height,
+                            thumbnail.pixels,
+                        ),
+                    );
+                }
+                None
+            }
         }
     }
 
     pub fn active_window(&self) -> Option<ToplevelHandle> {
-        // if self.active_workspaces.is_empty() {
-        //     return None;
-        // }
         let mut focused_toplevels: Vec<ToplevelHandle> = Vec::new();
-        // let active_workspaces = self.active_workspaces.clone();
+        let active_workspaces = self.active_workspaces();
         for (_, app_group) in self.active_toplevels.iter() {
             for (t_handle, t_info) in app_group.iter() {
+                // Compositors without workspace support leave this empty
                 if t_info.state.contains(&ToplevelState::Activated)
-                        // && active_workspaces
-                        //     .iter()
-                        //     .any(|workspace| t_info.workspace.contains(workspace))
-                        && t_info.output.iter().any(|x| {
-                            self.output_list.get(x).is_some_and(|_val| {
-                                true // TODO: Output stuff
-                                // val.name.as_ref().is_some_and(|n| *n == self.current_output)
-                            })
+                    && (t_info.workspace.is_empty()
+                        || active_workspaces
+                            .iter()
+                            .any(|workspace| t_info.workspace.contains(workspace)))
+                    && t_info.output.iter().any(|x| {
+                        self.output_list.get(x).is_some_and(|_val| {
+                            true // TODO: Output stuff
+                                 // val.name.as_ref().is_some_and(|n| *n == self.current_output)
                         })
+                    })
                 {
                     focused_toplevels.push(t_handle.clone());
                 }
@@ -778,4 +1230,127 @@ impl CompositorBackend {
         }
         focused_toplevels.first().cloned()
     }
+
+    fn track_focus(&mut self, handle: &ToplevelHandle, info: &CompositorToplevelInfo) {
+        if !info.state.contains(&ToplevelState::Activated) {
+            return;
+        }
+        let is_latest = self
+            .last_focused
+            .get(handle)
+            .is_some_and(|serial| *serial == self.focus_serial);
+        if !is_latest {
+            self.focus_serial += 1;
+            self.last_focused.insert(handle.clone(), self.focus_serial);
+        }
+    }
+
+    /// Focusing a window takes care of its request for attention, even if the compositor
+    /// doesn't clear the state
+    fn track_urgency(&mut self, handle: &ToplevelHandle, info: &CompositorToplevelInfo) {
+        if info.state.contains(&ToplevelState::Urgent)
+            && !info.state.contains(&ToplevelState::Activated)
+        {
+            self.urgent_since
+                .entry(handle.clone())
+                .or_insert_with(Instant::now);
+        } else {
+            self.urgent_since.remove(handle);
+        }
+    }
+
+    /// Since when the window wants attention, `None` if it doesn't
+    pub fn urgent_since(&self, handle: &ToplevelHandle) -> Option<Instant> {
+        self.urgent_since.get(handle).copied()
+    }
+
+    pub fn has_urgent(&self) -> bool {
+        !self.urgent_since.is_empty()
+    }
+
+    /// Whether thumbnails of the window can be captured. Windows from other protocols than
+    /// ext-foreign-toplevel-list are matched up with an ext one, which can still come up empty
+    pub fn can_capture(&self, _handle: &ToplevelHandle) -> bool {
+        self.capture_available
+    }
+
+    pub fn thumbnail(&self, handle: &ToplevelHandle) -> Option<image::Handle> {
+        self.thumbnails.get(handle).cloned()
+    }
+
+    /// App ids of everything that's open, in the order their oldest open window appeared
+    pub fn app_order(&self) -> Vec<String> {
+        let mut app_ids: Vec<String> = Vec::new();
+        for handle in self.toplevel_order.iter() {
+            let app_id = self
+                .active_toplevels
+                .iter()
+                .find(|(_, group)| group.contains_key(handle))
+                .map(|(app_id, _)| app_id);
+            if let Some(app_id) = app_id {
+                if !app_ids.contains(app_id) {
+                    app_ids.push(app_id.clone());
+                }
+            }
+        }
+        app_ids
+    }
+
+    /// Windows of `app_id` in the order they appeared
+    pub fn app_windows(&self, app_id: &str) -> Vec<(ToplevelHandle, CompositorToplevelInfo)> {
+        let Some(group) = self.active_toplevels.get(app_id) else {
+            return Vec::new();
+        };
+        self.toplevel_order
+            .iter()
+            .filter_map(|handle| group.get(handle).map(|info| (handle.clone(), info.clone())))
+            .collect()
+    }
+
+    /// When any window of `app_id` last had focus, higher is more recent
+    pub fn app_last_focused(&self, app_id: &str) -> u64 {
+        self.active_toplevels
+            .get(app_id)
+            .into_iter()
+            .flat_map(|group| group.keys())
+            .filter_map(|handle| self.last_focused.get(handle))
+            .max()
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn active_workspaces(&self) -> Vec<WorkspaceHandle> {
+        self.workspace_groups
+            .iter()
+            .flat_map(|group| group.workspaces.iter())
+            .filter(|workspace| workspace.is_active())
+            .map(|workspace| workspace.handle.clone())
+            .collect()
+    }
+
+    /// Every known output with a name to show for it, sorted by name
+    pub fn outputs(&self) -> Vec<(WlOutput, String)> {
+        let mut outputs = self
+            .output_list
+            .iter()
+            .map(|(output, info)| {
+                let name = info
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("{} {}", info.make, info.model));
+                (output.clone(), name)
+            })
+            .collect::<Vec<_>>();
+        outputs.sort_by(|(_, a), (_, b)| a.cmp(b));
+        outputs
+    }
+
+    /// Number of toplevels currently on the given workspace
+    pub fn workspace_toplevels(&self, workspace: &WorkspaceHandle) -> usize {
+        self.active_toplevels
+            .values()
+            .flat_map(|app_group| app_group.values())
+            .filter(|info| info.workspace.contains(workspace))
+            .count()
+    }
 }
@@ -7,15 +7,14 @@ use wayland_protocols_wlr::foreign_toplevel::v1::client::{
 
 use super::{AppData, ToplevelHandle, ToplevelHandleEvent, ToplevelManagerEvent, ToplevelState};
 
-impl From<zwlr_foreign_toplevel_handle_v1::State> for ToplevelState {
-    fn from(value: zwlr_foreign_toplevel_handle_v1::State) -> Self {
-        match value {
-            zwlr_foreign_toplevel_handle_v1::State::Maximized => Self::Maximized,
-            zwlr_foreign_toplevel_handle_v1::State::Minimized => Self::Minimized,
-            zwlr_foreign_toplevel_handle_v1::State::Activated => Self::Activated,
-            zwlr_foreign_toplevel_handle_v1::State::Fullscreen => Self::Fullscreen,
-            _ => todo!(),
-        }
+/// wlr has no attention state, compositors that want one use the plasma protocol
+fn toplevel_state(value: zwlr_foreign_toplevel_handle_v1::State) -> Option<ToplevelState> {
+    match value {
+        zwlr_foreign_toplevel_handle_v1::State::Maximized => Some(ToplevelState::Maximized),
+        zwlr_foreign_toplevel_handle_v1::State::Minimized => Some(ToplevelState::Minimized),
+        zwlr_foreign_toplevel_handle_v1::State::Activated => Some(ToplevelState::Activated),
+        zwlr_foreign_toplevel_handle_v1::State::Fullscreen => Some(ToplevelState::Fullscreen),
+        _ => None,
     }
 }
 
@@ -33,10 +32,13 @@ impl From<zwlr_foreign_toplevel_handle_v1::Event> for ToplevelHandleEvent {
             zwlr_foreign_toplevel_handle_v1::Event::State { state } => {
                 let mut r_state = HashSet::new();
                 for value in state.chunks_exact(4) {
-                    if let Ok(state) = zwlr_foreign_toplevel_handle_v1::State::try_from(
+                    if let Some(state) = zwlr_foreign_toplevel_handle_v1::State::try_from(
                         u32::from_ne_bytes(value[0..4].try_into().unwrap()),
-                    ) {
-                        r_state.insert(ToplevelState::from(state));
+                    )
+                    .ok()
+                    .and_then(toplevel_state)
+                    {
+                        r_state.insert(state);
                     }
                 }
                 Self::State { state: r_state }
@@ -44,7 +46,7 @@ impl From<zwlr_foreign_toplevel_handle_v1::Event> for ToplevelHandleEvent {
             zwlr_foreign_toplevel_handle_v1::Event::Done => Self::Done,
             zwlr_foreign_toplevel_handle_v1::Event::Closed => Self::Closed,
             zwlr_foreign_toplevel_handle_v1::Event::Parent { .. } => Self::None, // TODO: Not implemented
-            _ => todo!(),
+            _ => Self::None,
         }
     }
 }
@@ -56,7 +58,7 @@ impl From<zwlr_foreign_toplevel_manager_v1::Event> for ToplevelManagerEvent {
                 Self::Toplevel(ToplevelHandle::Zwlr(toplevel))
             }
             zwlr_foreign_toplevel_manager_v1::Event::Finished => Self::Finished,
-            _ => todo!(),
+            _ => Self::None,
         }
     }
 }
@@ -0,0 +1,304 @@
+// Backend-agnostic workspace tracking
+
+use std::collections::HashSet;
+
+use cosmic_protocols::workspace::v1::client::{
+    zcosmic_workspace_group_handle_v1::ZcosmicWorkspaceGroupHandleV1,
+    zcosmic_workspace_handle_v1::ZcosmicWorkspaceHandleV1,
+};
+use wayland_client::protocol::wl_output::WlOutput;
+
+use super::{
+    ext::workspace::v1::client::{
+        ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1,
+        ext_workspace_handle_v1::ExtWorkspaceHandleV1,
+    },
+    AppData, WaylandIncoming, WaylandWorkspaceRequest,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WorkspaceHandle {
+    Zcosmic(ZcosmicWorkspaceHandleV1),
+    Ext(ExtWorkspaceHandleV1),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WorkspaceGroupHandle {
+    Zcosmic(ZcosmicWorkspaceGroupHandleV1),
+    Ext(ExtWorkspaceGroupHandleV1),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WorkspaceState {
+    Active,
+    Urgent,
+    Hidden,
+}
+
+#[derive(Clone, Debug)]
+pub struct CompositorWorkspaceGroup {
+    pub handle: WorkspaceGroupHandle,
+    pub output: HashSet<WlOutput>,
+    pub workspaces: Vec<CompositorWorkspace>,
+    pub can_create_workspace: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct CompositorWorkspace {
+    pub handle: WorkspaceHandle,
+    /// Stable identifier, only provided by ext-workspace
+    pub id: Option<String>,
+    pub name: String,
+    pub coordinates: Vec<u32>,
+    pub state: HashSet<WorkspaceState>,
+    pub can_remove: bool,
+}
+
+impl CompositorWorkspace {
+    fn new(handle: WorkspaceHandle) -> Self {
+        Self {
+            handle,
+            id: None,
+            name: String::new(),
+            coordinates: Vec::new(),
+            state: HashSet::new(),
+            can_remove: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state.contains(&WorkspaceState::Active)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum WorkspaceManagerEvent {
+    WorkspaceGroup(WorkspaceGroupHandle),
+    Workspace(WorkspaceHandle),
+    Done,
+    Finished,
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum WorkspaceGroupEvent {
+    Capabilities { can_create_workspace: bool },
+    OutputEnter { output: WlOutput },
+    OutputLeave { output: WlOutput },
+    WorkspaceEnter(WorkspaceHandle),
+    WorkspaceLeave(WorkspaceHandle),
+    Remove,
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum WorkspaceHandleEvent {
+    Id { id: String },
+    Name { name: String },
+    Coordinates { coordinates: Vec<u32> },
+    State { state: HashSet<WorkspaceState> },
+    Capabilities { can_remove: bool },
+    Remove,
+    None,
+}
+
+#[derive(Clone, Debug)]
+struct WorkspaceGroupData {
+    handle: WorkspaceGroupHandle,
+    output: HashSet<WlOutput>,
+    workspaces: Vec<WorkspaceHandle>,
+    can_create_workspace: bool,
+}
+
+/// ext-workspace announces workspaces on the manager and only links them to groups
+/// afterwards, so workspaces are kept separately from the groups they belong to.
+#[derive(Clone, Debug, Default)]
+pub(super) struct WorkspaceManager {
+    groups: Vec<WorkspaceGroupData>,
+    workspaces: Vec<CompositorWorkspace>,
+}
+
+impl WorkspaceManager {
+    fn snapshot(&self) -> Vec<CompositorWorkspaceGroup> {
+        self.groups
+            .iter()
+            .map(|group| {
+                let mut workspaces = group
+                    .workspaces
+                    .iter()
+                    .filter_map(|handle| self.workspaces.iter().find(|x| x.handle == *handle))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                workspaces.sort_by(|a, b| a.coordinates.cmp(&b.coordinates));
+                CompositorWorkspaceGroup {
+                    handle: group.handle.clone(),
+                    output: group.output.clone(),
+                    workspaces,
+                    can_create_workspace: group.can_create_workspace,
+                }
+            })
+            .collect()
+    }
+
+    /// An output showing the group `workspace` belongs to
+    pub(super) fn output_of(&self, workspace: &WorkspaceHandle) -> Option<WlOutput> {
+        self.groups
+            .iter()
+            .find(|group| group.workspaces.contains(workspace))
+            .and_then(|group| group.output.iter().next().cloned())
+    }
+
+    /// The active workspace of the group shown on `output`
+    pub(super) fn active_workspace_on(&self, output: &WlOutput) -> Option<WorkspaceHandle> {
+        self.groups
+            .iter()
+            .filter(|group| group.output.contains(output))
+            .flat_map(|group| group.workspaces.iter())
+            .find(|handle| {
+                self.workspaces
+                    .iter()
+                    .any(|x| x.handle == **handle && x.is_active())
+            })
+            .cloned()
+    }
+}
+
+impl AppData {
+    pub(super) fn handle_workspace_manager_event(&mut self, evt: WorkspaceManagerEvent) {
+        match evt {
+            WorkspaceManagerEvent::WorkspaceGroup(handle) => {
+                self.workspace_state.groups.push(WorkspaceGroupData {
+                    handle,
+                    output: HashSet::new(),
+                    workspaces: Vec::new(),
+                    can_create_workspace: false,
+                });
+            }
+            WorkspaceManagerEvent::Workspace(handle) => {
+                self.workspace_state
+                    .workspaces
+                    .push(CompositorWorkspace::new(handle));
+            }
+            WorkspaceManagerEvent::Done => {
+                // Workspace state is only consistent once the manager is done
+                let _ = self
+                    .tx
+                    .unbounded_send(WaylandIncoming::Workspaces(self.workspace_state.snapshot()));
+            }
+            WorkspaceManagerEvent::Finished => {
+                self.workspace_state = WorkspaceManager::default();
+            }
+        }
+    }
+
+    pub(super) fn handle_workspace_group_event(
+        &mut self,
+        group: WorkspaceGroupHandle,
+        evt: WorkspaceGroupEvent,
+    ) {
+        let state = &mut self.workspace_state;
+        let Some(idx) = state.groups.iter().position(|x| x.handle == group) else {
+            log::warn!("Received event for dead workspace group");
+            return;
+        };
+        let data = &mut state.groups[idx];
+        match evt {
+            WorkspaceGroupEvent::Capabilities {
+                can_create_workspace,
+            } => {
+                data.can_create_workspace = can_create_workspace;
+            }
+            WorkspaceGroupEvent::OutputEnter { output } => {
+                data.output.insert(output);
+            }
+            WorkspaceGroupEvent::OutputLeave { output } => {
+                data.output.remove(&output);
+            }
+            WorkspaceGroupEvent::WorkspaceEnter(handle) => {
+                if !data.workspaces.contains(&handle) {
+                    data.workspaces.push(handle.clone());
+                }
+                // COSMIC creates workspaces through their group
+                if !state.workspaces.iter().any(|x| x.handle == handle) {
+                    state.workspaces.push(CompositorWorkspace::new(handle));
+                }
+            }
+            WorkspaceGroupEvent::WorkspaceLeave(handle) => {
+                data.workspaces.retain(|x| *x != handle);
+            }
+            WorkspaceGroupEvent::Remove => {
+                state.groups.remove(idx);
+            }
+            WorkspaceGroupEvent::None => {}
+        }
+    }
+
+    pub(super) fn handle_workspace_handle_event(
+        &mut self,
+        workspace: WorkspaceHandle,
+        evt: WorkspaceHandleEvent,
+    ) {
+        let state = &mut self.workspace_state;
+        let Some(idx) = state.workspaces.iter().position(|x| x.handle == workspace) else {
+            log::warn!("Received event for dead workspace");
+            return;
+        };
+        let data = &mut state.workspaces[idx];
+        match evt {
+            WorkspaceHandleEvent::Id { id } => {
+                data.id = Some(id);
+            }
+            WorkspaceHandleEvent::Name { name } => {
+                data.name = name;
+            }
+            WorkspaceHandleEvent::Coordinates { coordinates } => {
+                data.coordinates = coordinates;
+            }
+            WorkspaceHandleEvent::State { state } => {
+                data.state = state;
+            }
+            WorkspaceHandleEvent::Capabilities { can_remove } => {
+                data.can_remove = can_remove;
+            }
+            WorkspaceHandleEvent::Remove => {
+                state.workspaces.remove(idx);
+                for group in state.groups.iter_mut() {
+                    group.workspaces.retain(|x| *x != workspace);
+                }
+            }
+            WorkspaceHandleEvent::None => {}
+        }
+    }
+
+    pub(super) fn handle_workspace_request(&mut self, req: WaylandWorkspaceRequest) {
+        match req {
+            WaylandWorkspaceRequest::Activate(workspace) => {
+                log::debug!("Activating workspace!");
+                match workspace {
+                    WorkspaceHandle::Zcosmic(workspace) => workspace.activate(),
+                    WorkspaceHandle::Ext(workspace) => workspace.activate(),
+                }
+            }
+            WaylandWorkspaceRequest::Create { group, name } => {
+                log::debug!("Creating workspace {}!", name);
+                match group {
+                    WorkspaceGroupHandle::Zcosmic(group) => group.create_workspace(name),
+                    WorkspaceGroupHandle::Ext(group) => group.create_workspace(name),
+                }
+            }
+            WaylandWorkspaceRequest::Remove(workspace) => {
+                log::debug!("Removing workspace!");
+                match workspace {
+                    WorkspaceHandle::Zcosmic(workspace) => workspace.remove(),
+                    WorkspaceHandle::Ext(workspace) => workspace.remove(),
+                }
+            }
+        }
+        // Workspace requests are only applied on commit
+        if let Some(manager) = self.zcosmic_workspace_manager.as_ref() {
+            manager.commit();
+        }
+        if let Some(manager) = self.ext_workspace_manager.as_ref() {
+            manager.commit();
+        }
+    }
+}
@@ -0,0 +1,267 @@
+// Right click menu for tray items
+
+use std::collections::HashMap;
+
+use freedesktop_desktop_entry::get_languages_from_env;
+use iced::{
+    widget::{button, column, horizontal_rule, text},
+    Element, Length,
+};
+use wayland_client::protocol::wl_output::WlOutput;
+
+use crate::{design::component_theme::button_style, freedesktop::desktop_entry::EntryInfo};
+
+use super::{
+    compositor::{
+        CompositorBackend, CompositorToplevelInfo, ToplevelHandle, ToplevelState, WorkspaceHandle,
+        WorkspaceState,
+    },
+    AppTrayMessage,
+};
+
+pub const CONTEXT_MENU_WIDTH: u32 = 240;
+const ITEM_HEIGHT: u32 = 32;
+const SEPARATOR_HEIGHT: u32 = 9;
+const PADDING: u16 = 4;
+
+#[derive(Clone, Debug)]
+pub enum ContextMenuAction {
+    NewWindow(String),
+    DesktopAction { app_id: String, action: String },
+    DedicatedGpu(String),
+    TogglePin(String),
+    Close(ToplevelHandle),
+    CloseAll(String),
+    SetMaximized(ToplevelHandle, bool),
+    SetFullscreen(ToplevelHandle, bool),
+    MoveToWorkspace(ToplevelHandle, WorkspaceHandle),
+    MoveToOutput(ToplevelHandle, WlOutput),
+}
+
+pub enum MenuItem {
+    Button(String, ContextMenuAction),
+    Separator,
+}
+
+/// Everything the menu for `app_id` offers, grouped into sections
+pub fn items(
+    app_id: &str,
+    entry: Option<&EntryInfo>,
+    windows: &HashMap<ToplevelHandle, CompositorToplevelInfo>,
+    is_pinned: bool,
+    has_dedicated_gpu: bool,
+    backend: &CompositorBackend,
+) -> Vec<MenuItem> {
+    let locales = get_languages_from_env();
+    let can_launch = entry.is_some_and(|entry| entry.desktop_entry.exec().is_some());
+
+    let desktop_actions = entry
+        .and_then(|entry| {
+            entry.desktop_entry.actions().map(|actions| {
+                actions
+                    .into_iter()
+                    .filter(|action| !action.is_empty())
+                    .filter_map(|action| {
+                        let name = entry.desktop_entry.action_name(action, &locales)?;
+                        Some(MenuItem::Button(
+                            name.to_string(),
+                            ContextMenuAction::DesktopAction {
+                                app_id: app_id.to_string(),
+                                action: action.to_string(),
+                            },
+                        ))
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .unwrap_or_default();
+
+    let mut launch = Vec::new();
+    if can_launch {
+        let label = if windows.is_empty() {
+            "Launch"
+        } else {
+            "New window"
+        };
+        launch.push(MenuItem::Button(
+            label.to_string(),
+            ContextMenuAction::NewWindow(app_id.to_string()),
+        ));
+        // Only worth offering when there's a GPU other than the default one
+        if has_dedicated_gpu {
+            launch.push(MenuItem::Button(
+                "Launch using dedicated GPU".to_string(),
+                ContextMenuAction::DedicatedGpu(app_id.to_string()),
+            ));
+        }
+    }
+
+    let pin = vec![MenuItem::Button(
+        if is_pinned {
+            "Unpin from tray"
+        } else {
+            "Pin to tray"
+        }
+        .to_string(),
+        ContextMenuAction::TogglePin(app_id.to_string()),
+    )];
+
+    // With a single window the app menu doubles as its window menu
+    let window = match windows.iter().next() {
+        Some((handle, info)) if windows.len() == 1 => window_items(handle, info, backend),
+        _ => Vec::new(),
+    };
+
+    let close = match windows.len() {
+        0 => Vec::new(),
+        1 => vec![MenuItem::Button(
+            "Close".to_string(),
+            ContextMenuAction::Close(windows.keys().next().unwrap().clone()),
+        )],
+        _ => vec![MenuItem::Button(
+            "Close all windows".to_string(),
+            ContextMenuAction::CloseAll(app_id.to_string()),
+        )],
+    };
+
+    join_sections([desktop_actions, launch, pin, window, close])
+}
+
+/// Menu for a single window, as opened from the window list
+pub fn window_menu_items(
+    handle: &ToplevelHandle,
+    info: &CompositorToplevelInfo,
+    backend: &CompositorBackend,
+) -> Vec<MenuItem> {
+    let close = vec![MenuItem::Button(
+        "Close".to_string(),
+        ContextMenuAction::Close(handle.clone()),
+    )];
+    join_sections([window_items(handle, info, backend), close])
+}
+
+/// Window state and placement actions for `handle`
+fn window_items(
+    handle: &ToplevelHandle,
+    info: &CompositorToplevelInfo,
+    backend: &CompositorBackend,
+) -> Vec<MenuItem> {
+    let is_maximized = info.state.contains(&ToplevelState::Maximized);
+    let is_fullscreen = info.state.contains(&ToplevelState::Fullscreen);
+    let mut items = vec![MenuItem::Button(
+        if is_maximized {
+            "Unmaximize"
+        } else {
+            "Maximize"
+        }
+        .to_string(),
+        ContextMenuAction::SetMaximized(handle.clone(), !is_maximized),
+    )];
+    if handle.can_fullscreen() {
+        items.push(MenuItem::Button(
+            if is_fullscreen {
+                "Exit fullscreen"
+            } else {
+                "Fullscreen"
+            }
+            .to_string(),
+            ContextMenuAction::SetFullscreen(handle.clone(), !is_fullscreen),
+        ));
+    }
+    // wlr-foreign-toplevel-management has no way to move windows
+    if matches!(handle, ToplevelHandle::Zwlr(_)) {
+        return items;
+    }
+    let show_group_output = backend.workspace_groups.len() > 1;
+    for group in backend.workspace_groups.iter() {
+        let output_name = group.output.iter().next().and_then(|output| {
+            backend
+                .outputs()
+                .into_iter()
+                .find(|(x, _)| x == output)
+                .map(|(_, name)| name)
+        });
+        let workspaces = group
+            .workspaces
+            .iter()
+            .filter(|workspace| !workspace.state.contains(&WorkspaceState::Hidden))
+            .enumerate();
+        for (idx, workspace) in workspaces {
+            if info.workspace.contains(&workspace.handle) {
+                continue;
+            }
+            let name = if workspace.name.is_empty() {
+                (idx + 1).to_string()
+            } else {
+                workspace.name.clone()
+            };
+            let label = match output_name.as_ref() {
+                Some(output_name) if show_group_output => {
+                    format!("Move to workspace {} on {}", name, output_name)
+                }
+                _ => format!("Move to workspace {}", name),
+            };
+            items.push(MenuItem::Button(
+                label,
+                ContextMenuAction::MoveToWorkspace(handle.clone(), workspace.handle.clone()),
+            ));
+        }
+    }
+    let outputs = backend.outputs();
+    if outputs.len() > 1 && handle.can_move_to_output() {
+        for (output, name) in outputs {
+            if info.output.contains(&output) {
+                continue;
+            }
+            items.push(MenuItem::Button(
+                format!("Move to {}", name),
+                ContextMenuAction::MoveToOutput(handle.clone(), output),
+            ));
+        }
+    }
+    items
+}
+
+fn join_sections<const N: usize>(sections: [Vec<MenuItem>; N]) -> Vec<MenuItem> {
+    let mut items = Vec::new();
+    for section in sections {
+        if section.is_empty() {
+            continue;
+        }
+        if !items.is_empty() {
+            items.push(MenuItem::Separator);
+        }
+        items.extend(section);
+    }
+    items
+}
+
+/// Popup size needed to show `items`
+pub fn size(items: &[MenuItem]) -> (u32, u32) {
+    let height = items
+        .iter()
+        .map(|item| match item {
+            MenuItem::Button(..) => ITEM_HEIGHT,
+            MenuItem::Separator => SEPARATOR_HEIGHT,
+        })
+        .sum::<u32>();
+    (CONTEXT_MENU_WIDTH, height + 2 * PADDING as u32)
+}
+
+pub fn view<'a>(items: Vec<MenuItem>) -> Element<'a, AppTrayMessage> {
+    column(items.into_iter().map(|item| {
+        match item {
+            MenuItem::Button(label, action) => Element::from(
+                button(text(label).size(14))
+                    .on_press(AppTrayMessage::ContextMenuAction(action))
+                    .style(|theme, status| button_style(theme, status, false, 0))
+                    .width(Length::Fill)
+                    .height(ITEM_HEIGHT as u16),
+            ),
+            MenuItem::Separator => Element::from(horizontal_rule(SEPARATOR_HEIGHT as u16)),
+        }
+    }))
+    .padding(PADDING)
+    .width(Length::Fill)
+    .into()
+}
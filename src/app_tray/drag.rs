@@ -0,0 +1,151 @@
+// Dragging tray items around to reorder, pin and unpin them
+
+use iced::{event, mouse, window, Event, Point, Subscription};
+
+use crate::design::component_theme::PANEL_SIZE;
+
+use super::{AppTrayMessage, TrayItemKey};
+
+/// How far the pointer has to move before a press turns into a drag
+const DRAG_THRESHOLD: f32 = 8.0;
+
+#[derive(Clone, Debug)]
+pub enum DragEvent {
+    Moved(Point),
+    Left,
+    Pressed,
+    Released,
+}
+
+#[derive(Clone, Debug, Default)]
+enum DragState {
+    #[default]
+    Idle,
+    Pressed {
+        idx: usize,
+        item: TrayItemKey,
+        origin: Point,
+    },
+    Dragging {
+        idx: usize,
+        item: TrayItemKey,
+    },
+}
+
+/// What letting go of a dragged item amounts to
+#[derive(Clone, Debug)]
+pub enum Drop {
+    /// Dropped onto the tray, moving the item at `from` to `to` in the order items are shown
+    Move {
+        item: TrayItemKey,
+        from: usize,
+        to: usize,
+    },
+    /// Dropped outside of the panel
+    Remove { item: TrayItemKey },
+}
+
+/// Pointer tracking for the tray, positions are relative to the start of the tray
+#[derive(Clone, Debug, Default)]
+pub struct Drag {
+    cursor: Option<Point>,
+    state: DragState,
+}
+
+impl Drag {
+    /// Feed a pointer event, returning the drop when a drag ends.
+    /// `items` are the tray's buttons with their widths.
+    pub fn handle_event(&mut self, event: DragEvent, items: &[(TrayItemKey, f32)]) -> Option<Drop> {
+        match event {
+            DragEvent::Moved(position) => {
+                self.cursor = Some(position);
+                if let DragState::Pressed { idx, item, origin } = &self.state {
+                    if origin.distance(position) > DRAG_THRESHOLD {
+                        self.state = DragState::Dragging {
+                            idx: *idx,
+                            item: item.clone(),
+                        };
+                    }
+                }
+                None
+            }
+            DragEvent::Left => {
+                self.cursor = None;
+                None
+            }
+            DragEvent::Pressed => {
+                self.state = match self.cursor.filter(|cursor| is_over_panel(*cursor)) {
+                    Some(cursor) => match item_at(cursor.x, items) {
+                        Some(idx) => DragState::Pressed {
+                            idx,
+                            item: items[idx].0.clone(),
+                            origin: cursor,
+                        },
+                        None => DragState::Idle,
+                    },
+                    None => DragState::Idle,
+                };
+                None
+            }
+            DragEvent::Released => match std::mem::take(&mut self.state) {
+                DragState::Dragging { idx, item } => match self.target(items) {
+                    Some(to) => Some(Drop::Move {
+                        item,
+                        from: idx,
+                        to,
+                    }),
+                    None => Some(Drop::Remove { item }),
+                },
+                DragState::Idle | DragState::Pressed { .. } => None,
+            },
+        }
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        matches!(self.state, DragState::Dragging { .. })
+    }
+
+    /// Where the dragged item should be shown instead of its own spot, as `(from, to)`
+    pub fn preview(&self, items: &[(TrayItemKey, f32)]) -> Option<(usize, usize)> {
+        let DragState::Dragging { idx, .. } = &self.state else {
+            return None;
+        };
+        if *idx >= items.len() {
+            return None;
+        }
+        Some((*idx, self.target(items)?))
+    }
+
+    /// Index the dragged item would take, `None` when off the panel
+    fn target(&self, items: &[(TrayItemKey, f32)]) -> Option<usize> {
+        let cursor = self.cursor.filter(|cursor| is_over_panel(*cursor))?;
+        Some(item_at(cursor.x.max(0.0), items).unwrap_or(items.len().saturating_sub(1)))
+    }
+}
+
+/// Index of the item at `x`
+fn item_at(x: f32, items: &[(TrayItemKey, f32)]) -> Option<usize> {
+    let mut end = 0.0;
+    items.iter().position(|(_, width)| {
+        end += width;
+        x >= 0.0 && x < end
+    })
+}
+
+/// The pointer keeps reporting positions while a button is held, even once it leaves the panel
+fn is_over_panel(cursor: Point) -> bool {
+    (0.0..=PANEL_SIZE as f32).contains(&cursor.y)
+}
+
+pub fn subscription() -> Subscription<AppTrayMessage> {
+    event::listen_with(|event, _status, id: window::Id| {
+        let event = match event {
+            Event::Mouse(mouse::Event::CursorMoved { position }) => DragEvent::Moved(position),
+            Event::Mouse(mouse::Event::CursorLeft) => DragEvent::Left,
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => DragEvent::Pressed,
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => DragEvent::Released,
+            _ => return None,
+        };
+        Some(AppTrayMessage::Drag(id, event))
+    })
+}
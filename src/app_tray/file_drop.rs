@@ -0,0 +1,125 @@
+// Dropping files from other applications onto tray items
+
+use std::{borrow::Cow, path::Path};
+
+use freedesktop_desktop_entry::DesktopEntry;
+use iced::{
+    clipboard::{
+        dnd::{peek_dnd, DndAction},
+        mime::AllowedMimeTypes,
+    },
+    widget::dnd_destination::DndDestination,
+    Element, Task,
+};
+
+use crate::{freedesktop::mime::MIME_DATABASE, launcher::exec};
+
+use super::AppTrayMessage;
+
+const URI_LIST_MIME_TYPE: &str = "text/uri-list";
+
+/// Whether `desktop_entry` can be handed files at all, entries that can't don't take drops
+pub fn accepts_drops(desktop_entry: &DesktopEntry) -> bool {
+    let takes_files = desktop_entry
+        .exec()
+        .is_some_and(|exec| exec::takes_files(exec) != exec::FileArgs::None);
+    takes_files && !mime_types(desktop_entry).is_empty()
+}
+
+/// Whether every one of `files` has a type listed in the entry's `MimeType=`
+pub fn accepts_files(desktop_entry: &DesktopEntry, files: &[String]) -> bool {
+    let mime_types = mime_types(desktop_entry);
+    !files.is_empty()
+        && files.iter().all(|file| {
+            let file_type = file_mime_type(file);
+            let accepted = mime_types
+                .iter()
+                .any(|mime_type| MIME_DATABASE.is_a(&file_type, mime_type));
+            if !accepted {
+                log::debug!(
+                    "{} doesn't open {} ({})",
+                    desktop_entry.appid,
+                    file,
+                    file_type
+                );
+            }
+            accepted
+        })
+}
+
+fn mime_types<'a>(desktop_entry: &'a DesktopEntry) -> Vec<&'a str> {
+    desktop_entry
+        .desktop_entry("MimeType")
+        .map(|value| value.split(';').filter(|x| !x.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+fn file_mime_type(file: &str) -> String {
+    match file.split_once("://") {
+        Some((scheme, _)) if scheme != "file" => format!("x-scheme-handler/{}", scheme),
+        _ => exec::to_path(file)
+            .map(|path| MIME_DATABASE.guess(Path::new(&path)))
+            .unwrap_or_default(),
+    }
+}
+
+/// URIs in a `text/uri-list`, skipping comments
+pub fn parse_uri_list(data: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Files on offer in a drag
+#[derive(Clone, Debug)]
+pub struct UriList(pub Vec<String>);
+
+impl AllowedMimeTypes for UriList {
+    fn allowed() -> Cow<'static, [String]> {
+        Cow::Owned(vec![URI_LIST_MIME_TYPE.to_string()])
+    }
+}
+
+impl TryFrom<(Vec<u8>, String)> for UriList {
+    type Error = ();
+
+    fn try_from((data, _mime_type): (Vec<u8>, String)) -> Result<Self, Self::Error> {
+        Ok(Self(parse_uri_list(&data)))
+    }
+}
+
+/// Read what's being dragged over the item for `app_id`, so files it can't open are refused
+/// before they're dropped
+pub fn peek_offer(app_id: String) -> Task<AppTrayMessage> {
+    peek_dnd::<UriList>().map(move |files| AppTrayMessage::DndOffer {
+        app_id: app_id.clone(),
+        files: files.map(|files| files.0),
+    })
+}
+
+/// Make `content` take files dropped onto it for `app_id`, `refuse` once the files on offer
+/// turned out to be ones the app can't open
+pub fn drop_target<'a>(
+    app_id: &str,
+    refuse: bool,
+    content: impl Into<Element<'a, AppTrayMessage>>,
+) -> Element<'a, AppTrayMessage> {
+    let enter_app_id = app_id.to_string();
+    let drop_app_id = app_id.to_string();
+    DndDestination::new(content, vec![Cow::Borrowed(URI_LIST_MIME_TYPE)])
+        .action(if refuse {
+            DndAction::empty()
+        } else {
+            DndAction::Copy
+        })
+        .on_enter(move |_, _, _| AppTrayMessage::DndEnter(enter_app_id.clone()))
+        .on_leave(|| AppTrayMessage::DndLeave)
+        .on_finish(move |_, data, _, _, _| AppTrayMessage::DndDrop {
+            app_id: drop_app_id.clone(),
+            data,
+        })
+        .into()
+}
@@ -0,0 +1,295 @@
+// Badges and progress that apps publish for their launcher icons
+// https://wiki.ubuntu.com/Unity/LauncherAPI
+
+use std::{collections::HashMap, time::Instant};
+
+use iced::{
+    futures::{self, SinkExt, StreamExt},
+    Subscription,
+};
+use zbus::{message::Type, zvariant::OwnedValue, Connection, MatchRule, Message, MessageStream};
+
+use super::AppTrayMessage;
+
+const LAUNCHER_ENTRY_INTERFACE: &str = "com.canonical.Unity.LauncherEntry";
+
+/// Properties from one `Update` signal, only the ones that were sent are set
+#[derive(Clone, Debug, Default)]
+pub struct LauncherEntryUpdate {
+    count: Option<i64>,
+    count_visible: Option<bool>,
+    progress: Option<f64>,
+    progress_visible: Option<bool>,
+    urgent: Option<bool>,
+}
+
+impl From<HashMap<String, OwnedValue>> for LauncherEntryUpdate {
+    fn from(value: HashMap<String, OwnedValue>) -> Self {
+        let get_bool = |key: &str| value.get(key).and_then(|x| x.downcast_ref::<bool>().ok());
+        Self {
+            count: value
+                .get("count")
+                .and_then(|count| count.downcast_ref::<i64>().ok()),
+            count_visible: get_bool("count-visible"),
+            progress: value
+                .get("progress")
+                .and_then(|progress| progress.downcast_ref::<f64>().ok()),
+            progress_visible: get_bool("progress-visible"),
+            urgent: get_bool("urgent"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum LauncherEntryEvent {
+    Update {
+        /// Unique bus name of the app, so its entry can be cleared when it quits
+        sender: String,
+        /// Desktop file ID the update is for, without `application://` and `.desktop`
+        desktop_id: String,
+        update: LauncherEntryUpdate,
+    },
+    /// The app with this unique bus name left the bus
+    Vanished(String),
+}
+
+#[derive(Clone, Debug, Default)]
+struct LauncherEntry {
+    sender: String,
+    count: i64,
+    count_visible: bool,
+    progress: f64,
+    progress_visible: bool,
+    urgent_since: Option<Instant>,
+}
+
+/// Launcher entries keyed by desktop entry app_id
+#[derive(Clone, Debug, Default)]
+pub struct LauncherEntries {
+    entries: HashMap<String, LauncherEntry>,
+}
+
+impl LauncherEntries {
+    /// Apply an update for `app_id`, properties that weren't sent keep their value
+    pub fn update(&mut self, app_id: String, sender: String, update: LauncherEntryUpdate) {
+        let entry = self.entries.entry(app_id).or_default();
+        entry.sender = sender;
+        if let Some(count) = update.count {
+            entry.count = count;
+        }
+        if let Some(count_visible) = update.count_visible {
+            entry.count_visible = count_visible;
+        }
+        if let Some(progress) = update.progress {
+            entry.progress = progress.clamp(0.0, 1.0);
+        }
+        if let Some(progress_visible) = update.progress_visible {
+            entry.progress_visible = progress_visible;
+        }
+        match update.urgent {
+            Some(true) => {
+                entry.urgent_since.get_or_insert_with(Instant::now);
+            }
+            Some(false) => entry.urgent_since = None,
+            None => {}
+        }
+    }
+
+    /// An app quitting doesn't send a last update, so forget everything it published
+    pub fn remove_sender(&mut self, sender: &str) {
+        self.entries.retain(|_, entry| entry.sender != sender);
+    }
+
+    pub fn count(&self, app_id: &str) -> Option<i64> {
+        self.entries
+            .get(app_id)
+            .filter(|entry| entry.count_visible && entry.count > 0)
+            .map(|entry| entry.count)
+    }
+
+    /// Progress from 0 to 1
+    pub fn progress(&self, app_id: &str) -> Option<f64> {
+        self.entries
+            .get(app_id)
+            .filter(|entry| entry.progress_visible)
+            .map(|entry| entry.progress)
+    }
+
+    pub fn urgent_since(&self, app_id: &str) -> Option<Instant> {
+        self.entries
+            .get(app_id)
+            .and_then(|entry| entry.urgent_since)
+    }
+
+    pub fn has_urgent(&self) -> bool {
+        self.entries
+            .values()
+            .any(|entry| entry.urgent_since.is_some())
+    }
+}
+
+/// `application://org.gnome.Nautilus.desktop` to `org.gnome.Nautilus`
+fn desktop_id(app_uri: &str) -> &str {
+    let id = app_uri.strip_prefix("application://").unwrap_or(app_uri);
+    id.strip_suffix(".desktop").unwrap_or(id)
+}
+
+fn parse_message(message: &Message) -> Option<LauncherEntryEvent> {
+    let header = message.header();
+    match header.member()?.as_str() {
+        "Update" => {
+            let sender = header.sender()?.to_string();
+            let (app_uri, properties) = message
+                .body()
+                .deserialize::<(String, HashMap<String, OwnedValue>)>()
+                .map_err(|e| log::debug!("Malformed launcher entry update: {}", e))
+                .ok()?;
+            Some(LauncherEntryEvent::Update {
+                sender,
+                desktop_id: desktop_id(&app_uri).to_string(),
+                update: LauncherEntryUpdate::from(properties),
+            })
+        }
+        "NameOwnerChanged" => {
+            let (name, _old_owner, new_owner) = message
+                .body()
+                .deserialize::<(String, String, String)>()
+                .ok()?;
+            (name.starts_with(':') && new_owner.is_empty())
+                .then_some(LauncherEntryEvent::Vanished(name))
+        }
+        _ => None,
+    }
+}
+
+async fn listen(output: &mut futures::channel::mpsc::Sender<AppTrayMessage>) -> zbus::Result<()> {
+    let connection = Connection::session().await?;
+    let updates = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .interface(LAUNCHER_ENTRY_INTERFACE)?
+        .member("Update")?
+        .build();
+    let owner_changes = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender("org.freedesktop.DBus")?
+        .interface("org.freedesktop.DBus")?
+        .member("NameOwnerChanged")?
+        .build();
+    let mut messages = futures::stream::select(
+        MessageStream::for_match_rule(updates, &connection, None).await?,
+        MessageStream::for_match_rule(owner_changes, &connection, None).await?,
+    );
+    while let Some(message) = messages.next().await {
+        let Some(event) = message.ok().as_ref().and_then(parse_message) else {
+            continue;
+        };
+        if output
+            .send(AppTrayMessage::LauncherEntry(event))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Listen for launcher entry signals on the session bus
+pub fn subscription() -> Subscription<AppTrayMessage> {
+    Subscription::run(|| {
+        iced::stream::channel(16, |mut output| async move {
+            if let Err(e) = listen(&mut output).await {
+                log::warn!("Not listening for launcher entries: {}", e);
+            }
+            futures::future::pending::<()>().await;
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::zvariant::Value;
+
+    use super::*;
+
+    /// Properties the way an app sends them in an `Update` signal
+    fn properties(values: Vec<(&str, Value<'static>)>) -> HashMap<String, OwnedValue> {
+        values
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), OwnedValue::try_from(value).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_update() {
+        let update = LauncherEntryUpdate::from(properties(vec![
+            ("count", Value::from(3i64)),
+            ("count-visible", Value::from(true)),
+            ("progress", Value::from(0.5f64)),
+            ("progress-visible", Value::from(false)),
+            ("urgent", Value::from(true)),
+        ]));
+        assert_eq!(update.count, Some(3));
+        assert_eq!(update.count_visible, Some(true));
+        assert_eq!(update.progress, Some(0.5));
+        assert_eq!(update.progress_visible, Some(false));
+        assert_eq!(update.urgent, Some(true));
+    }
+
+    #[test]
+    fn parse_partial_update() {
+        let update = LauncherEntryUpdate::from(properties(vec![
+            ("progress", Value::from(0.25f64)),
+            // Wrong type, count is an int64
+            ("count", Value::from("3")),
+        ]));
+        assert_eq!(update.count, None);
+        assert_eq!(update.count_visible, None);
+        assert_eq!(update.progress, Some(0.25));
+        assert_eq!(update.urgent, None);
+    }
+
+    #[test]
+    fn apply_updates() {
+        let mut entries = LauncherEntries::default();
+        entries.update(
+            "org.gnome.Nautilus".to_string(),
+            ":1.42".to_string(),
+            LauncherEntryUpdate::from(properties(vec![
+                ("count", Value::from(2i64)),
+                ("count-visible", Value::from(true)),
+                ("progress", Value::from(1.5f64)),
+                ("progress-visible", Value::from(true)),
+            ])),
+        );
+        assert_eq!(entries.count("org.gnome.Nautilus"), Some(2));
+        assert_eq!(entries.progress("org.gnome.Nautilus"), Some(1.0));
+        assert!(!entries.has_urgent());
+
+        // Properties that aren't sent keep their value
+        entries.update(
+            "org.gnome.Nautilus".to_string(),
+            ":1.42".to_string(),
+            LauncherEntryUpdate::from(properties(vec![
+                ("progress-visible", Value::from(false)),
+                ("urgent", Value::from(true)),
+            ])),
+        );
+        assert_eq!(entries.count("org.gnome.Nautilus"), Some(2));
+        assert_eq!(entries.progress("org.gnome.Nautilus"), None);
+        assert!(entries.urgent_since("org.gnome.Nautilus").is_some());
+
+        entries.remove_sender(":1.42");
+        assert_eq!(entries.count("org.gnome.Nautilus"), None);
+        assert!(!entries.has_urgent());
+    }
+
+    #[test]
+    fn strip_desktop_id() {
+        assert_eq!(
+            desktop_id("application://org.gnome.Nautilus.desktop"),
+            "org.gnome.Nautilus"
+        );
+        assert_eq!(desktop_id("org.gnome.Nautilus"), "org.gnome.Nautilus");
+    }
+}
@@ -0,0 +1,108 @@
+// Feedback for launches that haven't shown a window yet
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::design::component_theme::{pulse, LaunchFeedback};
+
+/// How long to wait for a launched app's first toplevel before giving up
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(15);
+/// How long a failed launch is indicated for
+const FAILED_DURATION: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug)]
+enum LaunchState {
+    Pending {
+        token: Option<String>,
+        started: Instant,
+    },
+    Failed {
+        since: Instant,
+    },
+}
+
+/// Launches keyed by desktop entry app_id
+#[derive(Clone, Debug, Default)]
+pub struct Launches {
+    launches: HashMap<String, LaunchState>,
+}
+
+impl Launches {
+    /// Track a new launch, false if the app is already being launched
+    pub fn start(&mut self, app_id: &str) -> bool {
+        if self.is_pending(app_id) {
+            return false;
+        }
+        self.launches.insert(
+            app_id.to_string(),
+            LaunchState::Pending {
+                token: None,
+                started: Instant::now(),
+            },
+        );
+        true
+    }
+
+    pub fn set_token(&mut self, app_id: &str, new_token: String) {
+        if let Some(LaunchState::Pending { token, .. }) = self.launches.get_mut(app_id) {
+            *token = Some(new_token);
+        }
+    }
+
+    pub fn fail(&mut self, app_id: &str, failed_token: Option<&str>) {
+        let is_current = match self.launches.get(app_id) {
+            Some(LaunchState::Pending { token, .. }) => {
+                token.is_none() || failed_token.is_none() || token.as_deref() == failed_token
+            }
+            _ => false,
+        };
+        // Failures of an older launch shouldn't mark the current one
+        if is_current {
+            self.launches.insert(
+                app_id.to_string(),
+                LaunchState::Failed {
+                    since: Instant::now(),
+                },
+            );
+        }
+    }
+
+    /// A toplevel for `app_id` showed up, so its launch is done
+    pub fn finish(&mut self, app_id: &str) {
+        self.launches.remove(app_id);
+    }
+
+    /// Time out pending launches and clear old failures
+    pub fn tick(&mut self, now: Instant) {
+        self.launches.retain(|app_id, state| match state {
+            LaunchState::Pending { started, .. } => {
+                if now.duration_since(*started) > LAUNCH_TIMEOUT {
+                    log::warn!("No window appeared for {} after launching it", app_id);
+                    *state = LaunchState::Failed { since: now };
+                }
+                true
+            }
+            LaunchState::Failed { since } => now.duration_since(*since) < FAILED_DURATION,
+        });
+    }
+
+    pub fn is_pending(&self, app_id: &str) -> bool {
+        matches!(self.launches.get(app_id), Some(LaunchState::Pending { .. }))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.launches.is_empty()
+    }
+
+    pub fn feedback(&self, app_id: &str, now: Instant) -> LaunchFeedback {
+        match self.launches.get(app_id) {
+            Some(LaunchState::Pending { started, .. }) => {
+                LaunchFeedback::Pending(pulse(now.saturating_duration_since(*started)))
+            }
+            Some(LaunchState::Failed { .. }) => LaunchFeedback::Failed,
+            None => LaunchFeedback::None,
+        }
+    }
+}
@@ -1,32 +1,94 @@
 use std::{
-    collections::HashMap,
     rc::Rc,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use super::freedesktop::desktop_entry::DesktopEntryCache;
+use async_std::task::spawn_blocking;
+use bindings::{BindingState, MouseInput};
 use cctk::wayland_client::protocol::wl_seat::WlSeat;
 use compositor::{
-    CompositorBackend, CompositorToplevelInfo, ToplevelHandle, WaylandIncoming, WaylandOutgoing,
+    CompositorBackend, CompositorToplevelInfo, ToplevelHandle, ToplevelUpdate, WaylandIncoming,
+    WaylandOutgoing,
 };
-use iced::{Element, Task};
+use context_menu::ContextMenuAction;
+use drag::{Drag, DragEvent, Drop};
+use freedesktop_desktop_entry::get_languages_from_env;
+use iced::{keyboard::Modifiers, window, Element, Rectangle, Task};
+use launcher_entry::{LauncherEntries, LauncherEntryEvent};
+use launches::Launches;
+use previews::{PreviewEvent, Previews};
+use window_list::{WindowList, WindowListKey};
 
 use crate::{
-    config::PanelConfig,
+    config::{PanelConfig, TrayAction, UnpinnedOrder},
     design::{
-        component_theme::{button_style, PANEL_SIZE},
-        components::app_tray_button,
+        component_theme::{
+            app_tray_button_style, pulse, LaunchFeedback, APP_TRAY_LABEL_WIDTH, PANEL_PADDING,
+            PANEL_SIZE,
+        },
+        components::{app_tray_button, app_tray_labeled_button, IconBadges},
     },
-    freedesktop::desktop_entry::EntryInfo,
+    freedesktop::{
+        desktop_entry::EntryInfo,
+        icons::{default_icon_path, ImageHandle},
+    },
+    launcher::{
+        gpu::{self, Gpu},
+        LaunchInfo,
+    },
+    tooltip::{TooltipMessage, TooltipTarget},
 };
 
+mod bindings;
 pub mod compositor;
+pub mod context_menu;
+pub mod drag;
+mod file_drop;
+mod launcher_entry;
+mod launches;
+mod previews;
+pub mod window_list;
+
+/// Where the tray starts in the panel, after the panel's padding and the start menu
+pub const TRAY_OFFSET: f32 = PANEL_PADDING + PANEL_SIZE as f32;
+
+/// Which tray button something belongs to, apps whose windows aren't grouped have several
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrayItemKey {
+    pub app_id: String,
+    /// The window the button stands for on its own, `None` when it stands for the whole app
+    pub window: Option<ToplevelHandle>,
+}
+
+/// A single button in the tray, standing for either a whole app or one of its windows
+#[derive(Clone, Debug)]
+struct TrayItem<'a> {
+    key: TrayItemKey,
+    /// `None` for windows of apps without a desktop entry
+    entry: Option<EntryInfo<'a>>,
+    windows: Vec<(ToplevelHandle, CompositorToplevelInfo)>,
+}
 
 #[derive(Clone, Debug)]
 pub struct AppTray<'a> {
     de_cache: Rc<DesktopEntryCache<'a>>,
     backend: CompositorBackend,
     config: Arc<Mutex<PanelConfig>>,
+    gpus: Vec<Gpu>,
+    launches: Launches,
+    launcher_entries: LauncherEntries,
+    /// Time of the last animation frame
+    now: Instant,
+    window_list: Option<WindowList>,
+    previews: Previews,
+    drag: Drag,
+    /// Item a drag is over, and whether its app takes the files on offer once that's known
+    dnd_offer: Option<(String, Option<bool>)>,
+    binding_state: BindingState,
+    /// Icon for windows without a desktop entry
+    fallback_icon: Option<ImageHandle>,
 }
 
 #[derive(Clone, Debug)]
@@ -35,71 +97,287 @@ pub enum AppTrayMessage {
     WaylandOut(WaylandOutgoing),
     NewSeat(WlSeat),
     RemovedSeat(WlSeat),
-    ContextMenu(String),
+    ContextMenu(TrayItemKey),
+    Launch(String),
+    ContextMenuAction(ContextMenuAction),
+    Gpus(Vec<Gpu>),
+    Tick(Instant),
+    WindowList(TrayItemKey),
+    WindowListActivate(ToplevelHandle),
+    WindowListKey(WindowListKey),
+    WindowMenu {
+        item: TrayItemKey,
+        handle: ToplevelHandle,
+    },
+    ClosePopup,
+    Drag(window::Id, DragEvent),
+    DndEnter(String),
+    /// Files on offer while dragging over the item for `app_id`, `None` if they couldn't be read
+    DndOffer {
+        app_id: String,
+        files: Option<Vec<String>>,
+    },
+    DndLeave,
+    DndDrop {
+        app_id: String,
+        data: Vec<u8>,
+    },
+    LauncherEntry(LauncherEntryEvent),
+    Preview(PreviewEvent),
+    ShowPreviews(TrayItemKey),
+    /// A click or scroll on the tray item standing for `windows` of its app
+    TrayInput {
+        item: TrayItemKey,
+        windows: Vec<ToplevelHandle>,
+        input: MouseInput,
+    },
+    Modifiers(Modifiers),
+    Tooltip(TooltipMessage),
 }
 
 impl<'a> AppTray<'a> {
     pub fn new(config: Arc<Mutex<PanelConfig>>, de_cache: Rc<DesktopEntryCache<'a>>) -> Self {
+        let icon_theme = config
+            .lock()
+            .unwrap()
+            .inner
+            .icon_theme
+            .clone()
+            .unwrap_or_default();
+        let fallback_icon = default_icon_path(&icon_theme)
+            .as_deref()
+            .map(ImageHandle::from_path);
         Self {
             de_cache,
             backend: CompositorBackend::new(),
             config,
+            gpus: Vec::new(),
+            launches: Launches::default(),
+            launcher_entries: LauncherEntries::default(),
+            now: Instant::now(),
+            window_list: None,
+            previews: Previews::default(),
+            drag: Drag::default(),
+            dnd_offer: None,
+            binding_state: BindingState::default(),
+            fallback_icon,
         }
     }
 
+    /// Startup work that has to happen off the main thread
+    pub fn init() -> Task<AppTrayMessage> {
+        Task::perform(
+            spawn_blocking(|| {
+                gpu::gpus().unwrap_or_else(|e| {
+                    log::info!("switcheroo-control is unavailable: {}", e);
+                    Vec::new()
+                })
+            }),
+            AppTrayMessage::Gpus,
+        )
+    }
+
     pub fn view(&self) -> iced::Element<AppTrayMessage> {
         let active_window = self.backend.active_window();
-        // Get app tray apps
+        let mut tray_items = self.tray_items();
+        if let Some((from, to)) = self.drag.preview(&self.item_sizes(&tray_items)) {
+            let item = tray_items.remove(from);
+            tray_items.insert(to, item);
+        }
+        let app_tray_apps = tray_items.into_iter().map(|item| {
+            let width = self.item_width(&item);
+            let accepts_drops = item
+                .entry
+                .as_ref()
+                .is_some_and(|entry| file_drop::accepts_drops(&entry.desktop_entry));
+            let app_id = item.key.app_id.clone();
+            let button = iced::widget::container(self.view_tray_item(item, active_window.clone()))
+                // .width(Length::Fill)
+                .width(width as u16)
+                .height(PANEL_SIZE as u16)
+                .padding(4.0);
+            if accepts_drops {
+                let refuse = self
+                    .dnd_offer
+                    .as_ref()
+                    .is_some_and(|(offer_app_id, accepted)| {
+                        *offer_app_id == app_id && *accepted == Some(false)
+                    });
+                file_drop::drop_target(&app_id, refuse, button)
+            } else {
+                Element::from(button)
+            }
+        });
+        iced::widget::row(app_tray_apps).into()
+    }
+
+    /// Buttons shown in the tray, split up per window for apps that aren't grouped
+    fn tray_items(&self) -> Vec<TrayItem<'a>> {
+        let grouping = self.config.lock().unwrap().inner.app_tray.grouping;
+        self.tray_apps()
+            .into_iter()
+            .flat_map(|(app_id, entry, windows)| {
+                if windows.len() <= 1 || grouping.groups(windows.len()) {
+                    vec![TrayItem {
+                        key: TrayItemKey {
+                            app_id,
+                            window: None,
+                        },
+                        entry,
+                        windows,
+                    }]
+                } else {
+                    windows
+                        .into_iter()
+                        .map(|window| TrayItem {
+                            key: TrayItemKey {
+                                app_id: app_id.clone(),
+                                window: Some(window.0.clone()),
+                            },
+                            entry: entry.clone(),
+                            windows: vec![window],
+                        })
+                        .collect()
+                }
+            })
+            .collect()
+    }
+
+    /// Whether `item` shows a title next to its icon, windows without an entry always do
+    fn shows_title(&self, item: &TrayItem) -> bool {
+        let show_titles = self.config.lock().unwrap().inner.app_tray.show_titles;
+        !item.windows.is_empty() && (show_titles || item.entry.is_none())
+    }
+
+    /// Buttons showing a title are wider
+    fn item_width(&self, item: &TrayItem) -> u32 {
+        if self.shows_title(item) {
+            PANEL_SIZE + APP_TRAY_LABEL_WIDTH
+        } else {
+            PANEL_SIZE
+        }
+    }
+
+    fn item_sizes(&self, items: &[TrayItem]) -> Vec<(TrayItemKey, f32)> {
+        items
+            .iter()
+            .map(|item| (item.key.clone(), self.item_width(item) as f32))
+            .collect()
+    }
+
+    /// Apps shown in the tray, in order: favorites first, then everything else that's open
+    fn tray_apps(
+        &self,
+    ) -> Vec<(
+        String,
+        Option<EntryInfo<'a>>,
+        Vec<(ToplevelHandle, CompositorToplevelInfo)>,
+    )> {
         let panel_config = self.config.lock().unwrap().clone();
-        let app_tray_apps = panel_config
-            .inner
-            .app_tray
-            .favorites
+        let favorites = &panel_config.inner.app_tray.favorites;
+        // Open windows are always shown, favorites need an entry to be launched from
+        let with_entry = |app_id: String| {
+            let windows = self.backend.app_windows(&app_id);
+            let entry = self
+                .de_cache
+                .fuzzy_match(&app_id)
+                .filter(|entry| !entry.invisible);
+            (entry.is_some() || !windows.is_empty()).then_some((app_id, entry, windows))
+        };
+        let mut unpinned = self
+            .backend
+            .app_order()
+            .into_iter()
+            .filter(|app_id| !favorites.contains(app_id))
+            .filter_map(with_entry)
+            .collect::<Vec<_>>();
+        // Sorting is stable, so ties stay in the order they were opened
+        match panel_config.inner.app_tray.unpinned_order {
+            UnpinnedOrder::FirstOpened => {}
+            UnpinnedOrder::LastFocused => unpinned.sort_by_key(|(app_id, _, _)| {
+                std::cmp::Reverse(self.backend.app_last_focused(app_id))
+            }),
+            UnpinnedOrder::Alphabetical => {
+                let locales = get_languages_from_env();
+                unpinned.sort_by_cached_key(|(app_id, entry, _)| {
+                    entry
+                        .as_ref()
+                        .and_then(|entry| entry.desktop_entry.name(&locales))
+                        .map(|name| name.to_lowercase())
+                        .unwrap_or_else(|| app_id.to_lowercase())
+                })
+            }
+        }
+        favorites
             .iter()
-            .map(|x| {
-                let app_id = x.clone();
-                (
-                    app_id,
-                    self.backend
-                        .active_toplevels
-                        .get(x)
-                        .cloned()
-                        .unwrap_or_default(),
-                )
-            })
-            .chain(
-                self.backend
-                    .active_toplevels
-                    .iter()
-                    .filter_map(|(app_id, info)| {
-                        if panel_config.inner.app_tray.favorites.contains(app_id) {
-                            None
-                        } else {
-                            Some((app_id.clone(), info.clone()))
-                        }
-                    }),
-            )
-            .filter_map(|(app_id, group)| {
-                let entry = &self.de_cache.fuzzy_match(&app_id);
+            .cloned()
+            .filter_map(with_entry)
+            .chain(unpinned)
+            .collect()
+    }
 
-                self.view_tray_item(&app_id, entry.as_ref(), group, active_window.clone())
-            })
-            .map(|x| {
-                Element::from(
-                    iced::widget::container(x)
-                        // .width(Length::Fill)
-                        .width(PANEL_SIZE as u16)
-                        .height(PANEL_SIZE as u16)
-                        .padding(4.0),
-                )
-            });
-        iced::widget::row(app_tray_apps).into()
+    /// Where the tray item `key` is, relative to the start of the tray. When its windows were
+    /// grouped or split up since, the first item of its app stands in
+    pub fn item_rect(&self, key: &TrayItemKey) -> Option<Rectangle<i32>> {
+        let items = self.tray_items();
+        let idx = items
+            .iter()
+            .position(|item| item.key == *key)
+            .or_else(|| items.iter().position(|item| item.key.app_id == key.app_id))?;
+        let x = items[..idx]
+            .iter()
+            .map(|item| self.item_width(item))
+            .sum::<u32>();
+        Some(Rectangle {
+            x: x as i32,
+            y: 0,
+            width: self.item_width(&items[idx]) as i32,
+            height: PANEL_SIZE as i32,
+        })
     }
 
     pub fn handle_message(&mut self, message: AppTrayMessage) -> iced::Task<AppTrayMessage> {
         match message {
             AppTrayMessage::WaylandIn(evt) => {
-                self.backend.handle_incoming(evt).unwrap_or(Task::none())
+                match &evt {
+                    WaylandIncoming::Toplevel(ToplevelUpdate::Add(_, info)) => {
+                        let app_id = self
+                            .de_cache
+                            .fuzzy_match(&info.app_id)
+                            .map(|entry| entry.desktop_entry.appid.to_string())
+                            .unwrap_or_else(|| info.app_id.clone());
+                        self.launches.finish(&app_id);
+                    }
+                    WaylandIncoming::LaunchToken { app_id, token } => {
+                        self.launches.set_token(app_id, token.clone());
+                    }
+                    WaylandIncoming::LaunchFailed { app_id, token, .. } => {
+                        self.launches.fail(app_id, token.as_deref());
+                    }
+                    _ => {}
+                }
+                let task = self.backend.handle_incoming(evt).unwrap_or(Task::none());
+                // Nothing left to list once the last window is gone
+                let popup_app_id = self
+                    .window_list
+                    .as_ref()
+                    .map(|list| &list.item)
+                    .or(self.previews.shown())
+                    .map(|item| item.app_id.as_str());
+                if popup_app_id
+                    .is_some_and(|app_id| !self.backend.active_toplevels.contains_key(app_id))
+                {
+                    return Task::batch([task, Task::done(AppTrayMessage::ClosePopup)]);
+                }
+                task
+            }
+            // Letting go of a dragged item also counts as clicking it
+            AppTrayMessage::Launch(_)
+            | AppTrayMessage::WaylandOut(WaylandOutgoing::Toggle(_))
+            | AppTrayMessage::TrayInput { .. }
+                if self.drag.is_dragging() =>
+            {
+                Task::none()
             }
             AppTrayMessage::WaylandOut(evt) => {
                 self.backend.handle_outgoing(evt).unwrap_or(Task::none())
@@ -112,61 +390,669 @@ impl<'a> AppTray<'a> {
                 log::trace!("Removed seat!");
                 Task::none()
             }
-            AppTrayMessage::ContextMenu(_) => unreachable!(),
+            // Popups are opened and closed by the panel
+            message @ (AppTrayMessage::ContextMenu(_)
+            | AppTrayMessage::WindowList(_)
+            | AppTrayMessage::WindowMenu { .. }
+            | AppTrayMessage::ShowPreviews(_)
+            | AppTrayMessage::Tooltip(_)
+            | AppTrayMessage::ClosePopup) => {
+                log::warn!("App tray got a panel message: {:?}", message);
+                Task::none()
+            }
+            AppTrayMessage::Preview(event) => {
+                // Running apps have a tooltip as well, the previews replace it once they open
+                let tooltip = match &event {
+                    PreviewEvent::Enter(item) => {
+                        Some(TooltipMessage::Enter(TooltipTarget::TrayItem(item.clone())))
+                    }
+                    PreviewEvent::Leave => self
+                        .previews
+                        .hovered()
+                        .map(|item| TooltipMessage::Leave(TooltipTarget::TrayItem(item.clone()))),
+                    _ => None,
+                };
+                let task = self.previews.handle_event(event);
+                match tooltip {
+                    Some(tooltip) => {
+                        Task::batch([task, Task::done(AppTrayMessage::Tooltip(tooltip))])
+                    }
+                    None => task,
+                }
+            }
+            AppTrayMessage::TrayInput {
+                item,
+                windows,
+                input,
+            } => {
+                let action = self.binding_state.action(
+                    &self.config.lock().unwrap().inner.app_tray.mouse_bindings,
+                    input,
+                );
+                self.run_tray_action(action, item, windows)
+            }
+            AppTrayMessage::Modifiers(modifiers) => {
+                self.binding_state.set_modifiers(modifiers);
+                Task::none()
+            }
+            AppTrayMessage::WindowListActivate(handle) => {
+                self.backend
+                    .handle_outgoing(WaylandOutgoing::Activate(handle));
+                Task::done(AppTrayMessage::ClosePopup)
+            }
+            AppTrayMessage::WindowListKey(key) => {
+                let message = self
+                    .window_list
+                    .as_mut()
+                    .and_then(|list| list.handle_key(key, &self.backend));
+                message.map(Task::done).unwrap_or(Task::none())
+            }
+            AppTrayMessage::Launch(app_id) => self.launch(&app_id, None, None),
+            AppTrayMessage::ContextMenuAction(action) => {
+                let task = self.handle_context_menu_action(action);
+                Task::batch([task, Task::done(AppTrayMessage::ClosePopup)])
+            }
+            AppTrayMessage::Gpus(gpus) => {
+                log::debug!("GPUs: {:?}", gpus);
+                self.gpus = gpus;
+                Task::none()
+            }
+            AppTrayMessage::LauncherEntry(event) => {
+                match event {
+                    LauncherEntryEvent::Update {
+                        sender,
+                        desktop_id,
+                        update,
+                    } => {
+                        // The ID apps send doesn't always match their desktop file's name
+                        let app_id = self
+                            .de_cache
+                            .fuzzy_match(&desktop_id)
+                            .map(|entry| entry.desktop_entry.appid.to_string())
+                            .unwrap_or(desktop_id);
+                        self.launcher_entries.update(app_id, sender, update);
+                    }
+                    LauncherEntryEvent::Vanished(sender) => {
+                        self.launcher_entries.remove_sender(&sender)
+                    }
+                }
+                Task::none()
+            }
+            AppTrayMessage::Tick(now) => {
+                self.now = now;
+                self.launches.tick(now);
+                Task::none()
+            }
+            AppTrayMessage::DndEnter(app_id) => {
+                self.dnd_offer = Some((app_id.clone(), None));
+                let peek = file_drop::peek_offer(app_id.clone());
+                let active_window = self.backend.active_window();
+                let Some(group) = self.backend.active_toplevels.get(&app_id) else {
+                    return peek;
+                };
+                if active_window.is_some_and(|window| group.contains_key(&window)) {
+                    return peek;
+                }
+                // Bring the app up so the files can be dropped into one of its windows instead
+                let Some(window) = group.keys().next().cloned() else {
+                    return peek;
+                };
+                let activate = self
+                    .backend
+                    .handle_outgoing(WaylandOutgoing::Activate(window))
+                    .unwrap_or(Task::none());
+                Task::batch([peek, activate])
+            }
+            AppTrayMessage::DndOffer { app_id, files } => {
+                // The pointer may have moved on to another item while the offer was read
+                let Some((offer_app_id, accepted)) = self.dnd_offer.as_mut() else {
+                    return Task::none();
+                };
+                if *offer_app_id != app_id {
+                    return Task::none();
+                }
+                let entry = self.de_cache.fuzzy_match(&app_id);
+                *accepted = match (files, entry) {
+                    (Some(files), Some(entry)) => {
+                        Some(file_drop::accepts_files(&entry.desktop_entry, &files))
+                    }
+                    // Can't tell, leave it to the drop
+                    _ => None,
+                };
+                Task::none()
+            }
+            AppTrayMessage::DndLeave => {
+                self.dnd_offer = None;
+                Task::none()
+            }
+            AppTrayMessage::DndDrop { app_id, data } => {
+                self.dnd_offer = None;
+                let files = file_drop::parse_uri_list(&data);
+                let Some(entry) = self.de_cache.fuzzy_match(&app_id) else {
+                    log::info!("Ignoring files dropped onto {}, it has no entry", app_id);
+                    return Task::none();
+                };
+                if !file_drop::accepts_files(&entry.desktop_entry, &files) {
+                    log::info!("Ignoring dropped files, {} can't open them", app_id);
+                    return Task::none();
+                }
+                let Some(mut info) = LaunchInfo::new(&entry.desktop_entry) else {
+                    log::info!("Ignoring files dropped onto {}, it has no Exec", app_id);
+                    return Task::none();
+                };
+                info.files = files;
+                self.start_launch(info, None)
+            }
+            AppTrayMessage::Drag(_, event) => {
+                // Only presses and releases need to know what's in the tray
+                let items = match event {
+                    DragEvent::Pressed | DragEvent::Released => self.item_sizes(&self.tray_items()),
+                    DragEvent::Moved(_) | DragEvent::Left => Vec::new(),
+                };
+                if let Some(drop) = self.drag.handle_event(event, &items) {
+                    self.handle_drop(drop);
+                }
+                Task::none()
+            }
         }
     }
 
-    pub fn subscription(&self) -> iced::Subscription<AppTrayMessage> {
+    /// Carry out what a click or scroll on `item`, standing for `windows`, is bound to
+    fn run_tray_action(
+        &mut self,
+        action: TrayAction,
+        item: TrayItemKey,
+        windows: Vec<ToplevelHandle>,
+    ) -> Task<AppTrayMessage> {
+        let app_id = item.app_id.clone();
+        // Windows can close between drawing the item and clicking it
+        let windows = windows
+            .into_iter()
+            .filter(|window| {
+                self.backend
+                    .active_toplevels
+                    .get(&app_id)
+                    .is_some_and(|group| group.contains_key(window))
+            })
+            .collect::<Vec<_>>();
+        match action {
+            TrayAction::None => Task::none(),
+            TrayAction::Launch => self.launch(&app_id, None, None),
+            TrayAction::ContextMenu => Task::done(AppTrayMessage::ContextMenu(item)),
+            TrayAction::Toggle | TrayAction::CycleWindows | TrayAction::WindowList
+                if windows.is_empty() =>
+            {
+                self.launch(&app_id, None, None)
+            }
+            TrayAction::Toggle => match windows.as_slice() {
+                [window] => self
+                    .backend
+                    .handle_outgoing(WaylandOutgoing::Toggle(window.clone()))
+                    .unwrap_or(Task::none()),
+                _ => Task::done(AppTrayMessage::WindowList(item)),
+            },
+            TrayAction::CycleWindows => {
+                let next = self
+                    .backend
+                    .active_window()
+                    .and_then(|active| windows.iter().position(|window| *window == active))
+                    .map(|idx| (idx + 1) % windows.len())
+                    .unwrap_or(0);
+                self.backend
+                    .handle_outgoing(WaylandOutgoing::Activate(windows[next].clone()))
+                    .unwrap_or(Task::none())
+            }
+            TrayAction::MinimizeAll => {
+                for window in windows {
+                    self.backend
+                        .handle_outgoing(WaylandOutgoing::Minimize(window));
+                }
+                Task::none()
+            }
+            TrayAction::Close => {
+                for window in windows {
+                    self.backend.handle_outgoing(WaylandOutgoing::Close(window));
+                }
+                Task::none()
+            }
+            TrayAction::WindowList => Task::done(AppTrayMessage::WindowList(item)),
+        }
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_dragging()
+    }
+
+    /// Reorder, pin or unpin favorites depending on where an item was dropped
+    fn handle_drop(&mut self, drop: Drop) {
+        match drop {
+            Drop::Remove { item } => {
+                self.update_favorites(|favorites| favorites.retain(|x| *x != item.app_id));
+            }
+            Drop::Move { item, from, to } => {
+                let app_id = item.app_id;
+                let mut items = self
+                    .tray_items()
+                    .into_iter()
+                    .map(|item| item.key.app_id)
+                    .collect::<Vec<_>>();
+                if from >= items.len() {
+                    return;
+                }
+                let mut order = dedup(items.clone());
+                let item = items.remove(from);
+                items.insert(to.min(items.len()), item);
+                // Apps that aren't grouped have several buttons, they go where their first one is
+                let (Some(from), Some(idx)) = (
+                    order.iter().position(|x| *x == app_id),
+                    dedup(items).iter().position(|x| *x == app_id),
+                ) else {
+                    return;
+                };
+                let favorites = self.config.lock().unwrap().inner.app_tray.favorites.clone();
+                // Favorites are always shown first
+                let num_pinned = order.iter().filter(|x| favorites.contains(x)).count();
+                let is_pinned = favorites.contains(&app_id);
+                let (idx, num_pinned) = if is_pinned {
+                    (idx.min(num_pinned - 1), num_pinned)
+                } else if idx < num_pinned || (idx == num_pinned && idx != from) {
+                    (idx, num_pinned + 1)
+                } else {
+                    return;
+                };
+                let item = order.remove(from);
+                order.insert(idx, item);
+                order.truncate(num_pinned);
+                self.update_favorites(|favorites| {
+                    // Favorites without an entry aren't shown, keep them around anyway
+                    let hidden = favorites
+                        .iter()
+                        .filter(|x| !order.contains(x))
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    *favorites = order.into_iter().chain(hidden).collect();
+                });
+            }
+        }
+    }
+
+    /// Change the favorites and save them right away
+    fn update_favorites(&self, f: impl FnOnce(&mut Vec<String>)) {
+        let mut config = self.config.lock().unwrap();
+        f(&mut config.inner.app_tray.favorites);
+        if let Err(e) = config.save_to_file() {
+            log::error!("Could not save favorites: {}", e);
+        }
+    }
+
+    fn handle_context_menu_action(&mut self, action: ContextMenuAction) -> Task<AppTrayMessage> {
+        match action {
+            ContextMenuAction::NewWindow(app_id) => self.launch(&app_id, None, None),
+            ContextMenuAction::DesktopAction { app_id, action } => {
+                self.launch(&app_id, Some(&action), None)
+            }
+            ContextMenuAction::DedicatedGpu(app_id) => {
+                let gpu_idx = gpu::dedicated_gpu(&self.gpus);
+                self.launch(&app_id, None, gpu_idx)
+            }
+            ContextMenuAction::TogglePin(app_id) => {
+                self.update_favorites(|favorites| {
+                    if let Some(idx) = favorites.iter().position(|x| *x == app_id) {
+                        favorites.remove(idx);
+                    } else {
+                        favorites.push(app_id);
+                    }
+                });
+                Task::none()
+            }
+            ContextMenuAction::Close(window) => self
+                .backend
+                .handle_outgoing(WaylandOutgoing::Close(window))
+                .unwrap_or(Task::none()),
+            ContextMenuAction::SetMaximized(window, maximized) => self
+                .backend
+                .handle_outgoing(WaylandOutgoing::SetMaximized(window, maximized))
+                .unwrap_or(Task::none()),
+            ContextMenuAction::SetFullscreen(window, fullscreen) => self
+                .backend
+                .handle_outgoing(WaylandOutgoing::SetFullscreen(window, fullscreen))
+                .unwrap_or(Task::none()),
+            ContextMenuAction::MoveToWorkspace(window, workspace) => self
+                .backend
+                .handle_outgoing(WaylandOutgoing::MoveToWorkspace(window, workspace))
+                .unwrap_or(Task::none()),
+            ContextMenuAction::MoveToOutput(window, output) => self
+                .backend
+                .handle_outgoing(WaylandOutgoing::MoveToOutput(window, output))
+                .unwrap_or(Task::none()),
+            ContextMenuAction::CloseAll(app_id) => {
+                let windows = self
+                    .backend
+                    .active_toplevels
+                    .get(&app_id)
+                    .map(|group| group.keys().cloned().collect::<Vec<_>>())
+                    .unwrap_or_default();
+                for window in windows {
+                    self.backend.handle_outgoing(WaylandOutgoing::Close(window));
+                }
+                Task::none()
+            }
+        }
+    }
+
+    fn launch(
+        &mut self,
+        app_id: &str,
+        action: Option<&str>,
+        gpu_idx: Option<usize>,
+    ) -> Task<AppTrayMessage> {
+        let Some(info) = self
+            .de_cache
+            .fuzzy_match(app_id)
+            .and_then(|entry| match action {
+                Some(action) => LaunchInfo::for_action(&entry.desktop_entry, action),
+                None => LaunchInfo::new(&entry.desktop_entry),
+            })
+        else {
+            log::warn!("No Exec found for {}", app_id);
+            return Task::none();
+        };
+        self.start_launch(info, gpu_idx)
+    }
+
+    fn start_launch(
+        &mut self,
+        mut info: LaunchInfo,
+        gpu_idx: Option<usize>,
+    ) -> Task<AppTrayMessage> {
+        info.terminal_emulator = self.config.lock().unwrap().inner.terminal.clone();
+        // Indexes are into the GPUs found at startup, so the environment is picked from those
+        info.gpu_environment = gpu::environment(&self.gpus, gpu_idx, info.prefers_non_default_gpu);
+        // Terminal entries show up as the terminal's window, so there's nothing to wait for
+        if !info.terminal && !self.launches.start(&info.app_id) {
+            log::debug!("{} is already being launched", info.app_id);
+            return Task::none();
+        }
         self.backend
+            .handle_outgoing(WaylandOutgoing::Exec(info))
+            .unwrap_or(Task::none())
+    }
+
+    /// Start tracking the window list for `item`, returning the popup size it needs
+    pub fn open_window_list(&mut self, item: TrayItemKey) -> (u32, u32) {
+        let list = WindowList::new(item);
+        let size = WindowList::size(list.windows(&self.backend).len());
+        self.window_list = Some(list);
+        size
+    }
+
+    pub fn close_window_list(&mut self) {
+        self.window_list = None;
+    }
+
+    pub fn view_window_list(&self) -> iced::Element<AppTrayMessage> {
+        match self.window_list.as_ref() {
+            Some(list) => list.view(&self.backend),
+            None => iced::widget::horizontal_space().into(),
+        }
+    }
+
+    /// Start capturing the windows of `item`'s app, returning the popup size their previews need
+    pub fn open_previews(&mut self, item: TrayItemKey) -> Option<(u32, u32)> {
+        if !self.backend.active_toplevels.contains_key(&item.app_id) {
+            return None;
+        }
+        let windows = previews::capturable(&item.app_id, &self.backend);
+        if !windows.is_empty() {
+            self.backend
+                .handle_outgoing(WaylandOutgoing::Capture(windows));
+        }
+        let size = previews::size(&item.app_id, &self.backend);
+        self.previews.show(item);
+        Some(size)
+    }
+
+    pub fn close_previews(&mut self) {
+        if self.previews.shown().is_some() {
+            self.backend
+                .handle_outgoing(WaylandOutgoing::Capture(Vec::new()));
+        }
+        self.previews.hide();
+    }
+
+    pub fn view_previews(&self) -> iced::Element<AppTrayMessage> {
+        match self.previews.shown() {
+            Some(item) => previews::view(item, self.app_name(&item.app_id), &self.backend),
+            None => iced::widget::horizontal_space().into(),
+        }
+    }
+
+    /// Name from the app's desktop entry, or its app_id when it has none
+    fn app_name(&self, app_id: &str) -> String {
+        self.de_cache
+            .fuzzy_match(app_id)
+            .and_then(|entry| {
+                entry
+                    .desktop_entry
+                    .name(&get_languages_from_env())
+                    .map(|name| name.to_string())
+            })
+            .unwrap_or_else(|| app_id.to_string())
+    }
+
+    /// The app's name followed by the titles of the windows `item` stands for
+    pub fn tooltip(&self, item: &TrayItemKey) -> Vec<String> {
+        std::iter::once(self.app_name(&item.app_id))
+            .chain(
+                self.backend
+                    .app_windows(&item.app_id)
+                    .into_iter()
+                    .filter(|(handle, _)| item.window.as_ref().is_none_or(|x| x == handle))
+                    .map(|(_, info)| info.title)
+                    .filter(|title| !title.is_empty()),
+            )
+            .collect()
+    }
+
+    pub fn backend(&self) -> &CompositorBackend {
+        &self.backend
+    }
+
+    pub fn backend_mut(&mut self) -> &mut CompositorBackend {
+        &mut self.backend
+    }
+
+    pub fn subscription(&self) -> iced::Subscription<AppTrayMessage> {
+        let mut subscriptions = vec![self
+            .backend
             .wayland_subscription()
-            .map(AppTrayMessage::WaylandIn)
+            .map(AppTrayMessage::WaylandIn)];
+        if !self.launches.is_empty()
+            || self.backend.has_urgent()
+            || self.launcher_entries.has_urgent()
+        {
+            // Drives the pending launch and attention animations, and launch timeouts
+            subscriptions
+                .push(iced::time::every(Duration::from_millis(50)).map(AppTrayMessage::Tick));
+        }
+        if self.window_list.is_some() {
+            subscriptions.push(window_list::subscription());
+        }
+        subscriptions.push(drag::subscription());
+        subscriptions.push(bindings::subscription());
+        subscriptions.push(launcher_entry::subscription());
+        iced::Subscription::batch(subscriptions)
     }
 
     fn view_tray_item(
         &self,
-        app_id: &str,
-        entry: Option<&EntryInfo<'a>>,
-        app_info: HashMap<ToplevelHandle, CompositorToplevelInfo>,
+        item: TrayItem<'a>,
         active_window: Option<ToplevelHandle>,
-    ) -> Option<iced::widget::MouseArea<'a, AppTrayMessage>> {
-        if entry.is_none() || entry.is_some_and(|e| e.invisible) {
-            return None;
-        }
-        let is_active = active_window.is_some_and(|window| app_info.contains_key(&window));
-        let num_toplevels = app_info.len();
-        let image_handle = entry.and_then(|e| e.entry_image.clone());
-        Some(
-            iced::widget::mouse_area(
-                app_tray_button(image_handle, is_active, num_toplevels, false)
-                    .on_press_maybe(if num_toplevels == 0 {
-                        entry
-                            .and_then(|entry| entry.desktop_entry.exec())
-                            .map(|exec| {
-                                AppTrayMessage::WaylandOut(WaylandOutgoing::Exec(
-                                    app_id.to_string(),
-                                    exec.to_string(),
-                                ))
-                            })
-                    } else if num_toplevels == 1 {
-                        Some(AppTrayMessage::WaylandOut(WaylandOutgoing::Toggle(
-                            app_info.keys().next().unwrap().clone(),
-                        )))
-                    } else {
-                        None
-                        // TODO
-                    })
-                    .style(move |theme, status| {
-                        button_style(theme, status, is_active, num_toplevels)
-                    }),
+    ) -> iced::widget::MouseArea<'a, AppTrayMessage> {
+        let shows_title = self.shows_title(&item);
+        let TrayItem {
+            key,
+            entry,
+            windows,
+        } = item;
+        let app_id = key.app_id.clone();
+        let is_active =
+            active_window.is_some_and(|window| windows.iter().any(|(handle, _)| *handle == window));
+        let num_toplevels = windows.len();
+        let feedback = entry
+            .as_ref()
+            .map(|entry| self.launches.feedback(&entry.desktop_entry.appid, self.now))
+            .unwrap_or(LaunchFeedback::None);
+        let entry_app_id = entry
+            .as_ref()
+            .map(|entry| entry.desktop_entry.appid.to_string());
+        let badges = entry_app_id
+            .as_deref()
+            .map(|app_id| IconBadges {
+                count: self.launcher_entries.count(app_id),
+                progress: self.launcher_entries.progress(app_id),
+            })
+            .unwrap_or_default();
+        let urgency = windows
+            .iter()
+            .filter_map(|(handle, _)| self.backend.urgent_since(handle))
+            .chain(
+                entry_app_id
+                    .as_deref()
+                    .and_then(|app_id| self.launcher_entries.urgent_since(app_id)),
             )
-            .on_right_press(AppTrayMessage::ContextMenu(app_id.to_string())),
+            .min()
+            .map(|since| pulse(self.now.saturating_duration_since(since)));
+        // Launching again while a launch is pending would only start a second copy
+        let is_launching = matches!(feedback, LaunchFeedback::Pending(_));
+        let label = match windows.as_slice() {
+            [(_, info)] if !info.title.is_empty() => info.title.clone(),
+            // Grouped buttons stand for the whole app
+            _ => entry
+                .as_ref()
+                .and_then(|entry| entry.desktop_entry.name(&get_languages_from_env()))
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| app_id.clone()),
+        };
+        let image_handle = entry
+            .as_ref()
+            .and_then(|entry| entry.entry_image.clone())
+            .or_else(|| self.fallback_icon.clone())
+            .unwrap_or_else(|| ImageHandle::from_name(&label));
+        let button = if shows_title {
+            app_tray_labeled_button(Some(image_handle), label, is_active, num_toplevels, badges)
+        } else {
+            app_tray_button(Some(image_handle), is_active, num_toplevels, false, badges)
+        };
+        // Items that can't be launched and have no windows are shown as disabled
+        let is_enabled = num_toplevels > 0
+            || (!is_launching
+                && entry
+                    .as_ref()
+                    .is_some_and(|entry| entry.desktop_entry.exec().is_some()));
+        let handles = windows
+            .iter()
+            .map(|(handle, _)| handle.clone())
+            .collect::<Vec<_>>();
+        let hover_key = key.clone();
+        let input = move |input| AppTrayMessage::TrayInput {
+            item: key.clone(),
+            windows: handles.clone(),
+            input,
+        };
+        let mouse_area = iced::widget::mouse_area(
+            button
+                .on_press_maybe(is_enabled.then(|| input(MouseInput::Left)))
+                .style(move |theme, status| {
+                    app_tray_button_style(
+                        theme,
+                        status,
+                        is_active,
+                        num_toplevels,
+                        feedback,
+                        urgency,
+                    )
+                }),
         )
-        // .on_press_maybe(if toplevels.is_empty() {
-        //     launch_on_preferred_gpu(desktop_info, gpus)
-        // } else if toplevels.len() == 1 {
-        //     toplevels.first().map(|t| Message::Toggle(t.0.clone()))
-        // } else {
-        //     Some(Message::TopLevelListPopup((*id).into(), window_id))
-        // })
+        .on_middle_press(input(MouseInput::Middle))
+        .on_right_press(input(MouseInput::Right))
+        .on_scroll(move |delta| input(MouseInput::Scroll(delta)));
+        // Launchers without windows have nothing to preview, they only get a tooltip
+        if num_toplevels == 0 {
+            let target = TooltipTarget::TrayItem(hover_key);
+            return mouse_area
+                .on_enter(AppTrayMessage::Tooltip(TooltipMessage::Enter(
+                    target.clone(),
+                )))
+                .on_exit(AppTrayMessage::Tooltip(TooltipMessage::Leave(target)));
+        }
+        mouse_area
+            .on_enter(AppTrayMessage::Preview(PreviewEvent::Enter(hover_key)))
+            .on_exit(AppTrayMessage::Preview(PreviewEvent::Leave))
+    }
+
+    fn context_menu_items(&self, app_id: &str) -> Vec<context_menu::MenuItem> {
+        let entry = self.de_cache.fuzzy_match(app_id);
+        let windows = self
+            .backend
+            .active_toplevels
+            .get(app_id)
+            .cloned()
+            .unwrap_or_default();
+        let is_pinned = self
+            .config
+            .lock()
+            .unwrap()
+            .inner
+            .app_tray
+            .favorites
+            .iter()
+            .any(|x| x == app_id);
+        context_menu::items(
+            app_id,
+            entry.as_ref(),
+            &windows,
+            is_pinned,
+            gpu::dedicated_gpu(&self.gpus).is_some(),
+            &self.backend,
+        )
+    }
+
+    /// Popup size the context menu for `app_id` needs
+    pub fn context_menu_size(&self, app_id: &str) -> (u32, u32) {
+        context_menu::size(&self.context_menu_items(app_id))
+    }
+
+    pub fn view_context_menu(&self, app_id: &str) -> iced::Element<AppTrayMessage> {
+        context_menu::view(self.context_menu_items(app_id))
+    }
+
+    fn window_menu_items(&self, handle: &ToplevelHandle) -> Vec<context_menu::MenuItem> {
+        self.backend
+            .active_toplevels
+            .values()
+            .find_map(|group| group.get(handle))
+            .map(|info| context_menu::window_menu_items(handle, info, &self.backend))
+            .unwrap_or_default()
+    }
+
+    /// Popup size the menu for a single window needs
+    pub fn window_menu_size(&self, handle: &ToplevelHandle) -> (u32, u32) {
+        context_menu::size(&self.window_menu_items(handle))
+    }
+
+    pub fn view_window_menu(&self, handle: &ToplevelHandle) -> iced::Element<AppTrayMessage> {
+        context_menu::view(self.window_menu_items(handle))
+    }
+}
+
+/// `app_ids` with repeats removed, keeping the first of each
+fn dedup(app_ids: Vec<String>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for app_id in app_ids {
+        if !result.contains(&app_id) {
+            result.push(app_id);
+        }
     }
+    result
 }
@@ -0,0 +1,211 @@
+// Popup with a live thumbnail of each window, shown after hovering a tray item for a moment
+
+use std::time::Duration;
+
+use iced::{
+    widget::{button, column, container, image, mouse_area, row, text},
+    Alignment, ContentFit, Element, Task,
+};
+
+use crate::design::component_theme::button_style;
+
+use super::{
+    compositor::{
+        capture::{THUMBNAIL_HEIGHT, THUMBNAIL_WIDTH},
+        CompositorBackend, ToplevelHandle,
+    },
+    window_list::WindowList,
+    AppTrayMessage, TrayItemKey,
+};
+
+/// How long the pointer has to rest on a tray item before its previews show up, the item's
+/// tooltip comes first
+const HOVER_DELAY: Duration = Duration::from_millis(1200);
+/// Moving from the tray item to the popup crosses a gap, so closing waits a bit
+const LEAVE_DELAY: Duration = Duration::from_millis(300);
+
+const MAX_COLUMNS: usize = 4;
+const HEADER_HEIGHT: u32 = 24;
+const TITLE_HEIGHT: u32 = 20;
+const CARD_PADDING: u16 = 6;
+const SPACING: u16 = 4;
+const PADDING: u16 = 4;
+const CARD_WIDTH: u32 = THUMBNAIL_WIDTH + 2 * CARD_PADDING as u32;
+const CARD_HEIGHT: u32 = THUMBNAIL_HEIGHT + SPACING as u32 + TITLE_HEIGHT + 2 * CARD_PADDING as u32;
+
+#[derive(Clone, Debug)]
+pub enum PreviewEvent {
+    /// The pointer went onto this tray item
+    Enter(TrayItemKey),
+    Leave,
+    PopupEnter,
+    PopupLeave,
+    /// A delay started by the hover change with this serial ran out
+    Timeout(u64),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Previews {
+    /// Tray item the pointer is on
+    hovered: Option<TrayItemKey>,
+    popup_hovered: bool,
+    /// Tray item whose previews are open
+    shown: Option<TrayItemKey>,
+    /// Bumped on every hover change, so delays that were overtaken can be ignored
+    serial: u64,
+}
+
+impl Previews {
+    pub fn handle_event(&mut self, event: PreviewEvent) -> Task<AppTrayMessage> {
+        match event {
+            PreviewEvent::Enter(item) => {
+                // Moving along the tray while previews are open swaps them right away
+                let swap = self.shown.as_ref().is_some_and(|shown| *shown != item);
+                self.hovered = Some(item.clone());
+                if swap {
+                    self.serial += 1;
+                    Task::done(AppTrayMessage::ShowPreviews(item))
+                } else {
+                    self.wait(HOVER_DELAY)
+                }
+            }
+            PreviewEvent::Leave => {
+                self.hovered = None;
+                self.wait(LEAVE_DELAY)
+            }
+            PreviewEvent::PopupEnter => {
+                self.popup_hovered = true;
+                Task::none()
+            }
+            PreviewEvent::PopupLeave => {
+                self.popup_hovered = false;
+                self.wait(LEAVE_DELAY)
+            }
+            PreviewEvent::Timeout(serial) if serial == self.serial => {
+                match (&self.hovered, &self.shown) {
+                    (Some(hovered), Some(shown)) if hovered == shown => Task::none(),
+                    (Some(hovered), _) => Task::done(AppTrayMessage::ShowPreviews(hovered.clone())),
+                    (None, Some(_)) if !self.popup_hovered => {
+                        Task::done(AppTrayMessage::ClosePopup)
+                    }
+                    _ => Task::none(),
+                }
+            }
+            PreviewEvent::Timeout(_) => Task::none(),
+        }
+    }
+
+    fn wait(&mut self, delay: Duration) -> Task<AppTrayMessage> {
+        self.serial += 1;
+        let serial = self.serial;
+        Task::perform(async_std::task::sleep(delay), move |_| {
+            AppTrayMessage::Preview(PreviewEvent::Timeout(serial))
+        })
+    }
+
+    pub fn hovered(&self) -> Option<&TrayItemKey> {
+        self.hovered.as_ref()
+    }
+
+    pub fn shown(&self) -> Option<&TrayItemKey> {
+        self.shown.as_ref()
+    }
+
+    pub fn show(&mut self, item: TrayItemKey) {
+        self.shown = Some(item);
+    }
+
+    pub fn hide(&mut self) {
+        self.shown = None;
+        self.popup_hovered = false;
+    }
+}
+
+/// Windows of `app_id` that thumbnails can be captured for
+pub fn capturable(app_id: &str, backend: &CompositorBackend) -> Vec<ToplevelHandle> {
+    backend
+        .app_windows(app_id)
+        .into_iter()
+        .map(|(handle, _)| handle)
+        .filter(|handle| backend.can_capture(handle))
+        .collect()
+}
+
+/// Popup size needed for the previews of `app_id`
+pub fn size(app_id: &str, backend: &CompositorBackend) -> (u32, u32) {
+    let num_windows = backend.app_windows(app_id).len();
+    if capturable(app_id, backend).is_empty() {
+        let (width, height) = WindowList::size(num_windows);
+        return (width, height + HEADER_HEIGHT);
+    }
+    let columns = num_windows.clamp(1, MAX_COLUMNS) as u32;
+    let rows = num_windows.max(1).div_ceil(MAX_COLUMNS) as u32;
+    (
+        columns * CARD_WIDTH + (columns - 1) * SPACING as u32 + 2 * PADDING as u32,
+        HEADER_HEIGHT + rows * CARD_HEIGHT + rows * SPACING as u32 + 2 * PADDING as u32,
+    )
+}
+
+/// The app's name over thumbnails of its windows, or just their titles when nothing can be
+/// captured
+pub fn view<'a>(
+    item: &TrayItemKey,
+    name: String,
+    backend: &CompositorBackend,
+) -> Element<'a, AppTrayMessage> {
+    let app_id = item.app_id.as_str();
+    let header = container(text(name).size(14))
+        .padding([0, PADDING + CARD_PADDING])
+        .center_y(HEADER_HEIGHT as u16);
+    let content = if capturable(app_id, backend).is_empty() {
+        column![header, WindowList::new(item.clone()).view(backend)].into()
+    } else {
+        let active_window = backend.active_window();
+        let cards = backend
+            .app_windows(app_id)
+            .into_iter()
+            .map(|(handle, info)| {
+                let is_active = active_window.as_ref() == Some(&handle);
+                let title = if info.title.is_empty() {
+                    info.app_id.clone()
+                } else {
+                    info.title.clone()
+                };
+                // Windows show up empty until their first frame arrives
+                let thumbnail: Element<'a, AppTrayMessage> = match backend.thumbnail(&handle) {
+                    Some(thumbnail) => image(thumbnail).content_fit(ContentFit::Contain).into(),
+                    None => iced::widget::horizontal_space().into(),
+                };
+                Element::from(
+                    button(
+                        column![
+                            container(thumbnail)
+                                .center_x(THUMBNAIL_WIDTH as u16)
+                                .center_y(THUMBNAIL_HEIGHT as u16),
+                            text(title).size(12).height(TITLE_HEIGHT as u16),
+                        ]
+                        .spacing(SPACING)
+                        .align_x(Alignment::Center),
+                    )
+                    .on_press(AppTrayMessage::WindowListActivate(handle))
+                    .style(move |theme, status| button_style(theme, status, is_active, 1))
+                    .padding(CARD_PADDING)
+                    .width(CARD_WIDTH as u16)
+                    .height(CARD_HEIGHT as u16),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut rows = vec![Element::from(header)];
+        let mut cards = cards.into_iter().peekable();
+        while cards.peek().is_some() {
+            rows.push(Element::from(
+                row(cards.by_ref().take(MAX_COLUMNS)).spacing(SPACING),
+            ));
+        }
+        column(rows).spacing(SPACING).padding(PADDING).into()
+    };
+    mouse_area(content)
+        .on_enter(AppTrayMessage::Preview(PreviewEvent::PopupEnter))
+        .on_exit(AppTrayMessage::Preview(PreviewEvent::PopupLeave))
+        .into()
+}
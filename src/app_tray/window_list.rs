@@ -0,0 +1,166 @@
+// Popup listing every window of an app with more than one open
+
+use iced::{
+    keyboard::{self, key::Named, Key, Modifiers},
+    widget::{button, column, mouse_area, row, text},
+    Alignment, Element, Length, Subscription,
+};
+
+use crate::design::component_theme::button_style;
+
+use super::{
+    compositor::{
+        CompositorBackend, CompositorToplevelInfo, ToplevelHandle, ToplevelState, WaylandOutgoing,
+    },
+    AppTrayMessage, TrayItemKey,
+};
+
+pub const WINDOW_LIST_WIDTH: u32 = 320;
+const ROW_HEIGHT: u32 = 36;
+const PADDING: u16 = 4;
+
+#[derive(Clone, Debug)]
+pub enum WindowListKey {
+    Previous,
+    Next,
+    Activate,
+    Close,
+    Menu,
+    Dismiss,
+}
+
+#[derive(Clone, Debug)]
+pub struct WindowList {
+    /// Tray item the list was opened from
+    pub item: TrayItemKey,
+    selected: usize,
+}
+
+impl WindowList {
+    pub fn new(item: TrayItemKey) -> Self {
+        Self { item, selected: 0 }
+    }
+
+    /// Popup size needed to list `num_windows` windows
+    pub fn size(num_windows: usize) -> (u32, u32) {
+        (
+            WINDOW_LIST_WIDTH,
+            num_windows.max(1) as u32 * ROW_HEIGHT + 2 * PADDING as u32,
+        )
+    }
+
+    /// Windows of the app in the order they were opened, so rows don't move when titles change
+    pub fn windows(
+        &self,
+        backend: &CompositorBackend,
+    ) -> Vec<(ToplevelHandle, CompositorToplevelInfo)> {
+        backend.app_windows(&self.item.app_id)
+    }
+
+    /// What a key press in the popup amounts to
+    pub fn handle_key(
+        &mut self,
+        key: WindowListKey,
+        backend: &CompositorBackend,
+    ) -> Option<AppTrayMessage> {
+        let windows = self.windows(backend);
+        if windows.is_empty() {
+            return Some(AppTrayMessage::ClosePopup);
+        }
+        self.selected = self.selected.min(windows.len() - 1);
+        match key {
+            WindowListKey::Previous => {
+                self.selected = self.selected.checked_sub(1).unwrap_or(windows.len() - 1);
+                None
+            }
+            WindowListKey::Next => {
+                self.selected = (self.selected + 1) % windows.len();
+                None
+            }
+            WindowListKey::Activate => Some(AppTrayMessage::WindowListActivate(
+                windows[self.selected].0.clone(),
+            )),
+            WindowListKey::Close => Some(AppTrayMessage::WaylandOut(WaylandOutgoing::Close(
+                windows[self.selected].0.clone(),
+            ))),
+            WindowListKey::Menu => Some(AppTrayMessage::WindowMenu {
+                item: self.item.clone(),
+                handle: windows[self.selected].0.clone(),
+            }),
+            WindowListKey::Dismiss => Some(AppTrayMessage::ClosePopup),
+        }
+    }
+
+    pub fn view<'a>(&self, backend: &CompositorBackend) -> Element<'a, AppTrayMessage> {
+        let active_window = backend.active_window();
+        let rows = self
+            .windows(backend)
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (handle, info))| {
+                let is_selected = idx == self.selected;
+                let is_active = active_window.as_ref() == Some(&handle);
+                let title = if info.title.is_empty() {
+                    info.app_id.clone()
+                } else {
+                    info.title.clone()
+                };
+                let state = if backend.urgent_since(&handle).is_some() {
+                    "Needs attention"
+                } else if info.state.contains(&ToplevelState::Minimized) {
+                    "Minimized"
+                } else if info.state.contains(&ToplevelState::Fullscreen) {
+                    "Fullscreen"
+                } else if info.state.contains(&ToplevelState::Maximized) {
+                    "Maximized"
+                } else {
+                    ""
+                };
+                let activate = button(
+                    row![
+                        text(if is_active { "•" } else { " " }).width(12),
+                        text(title).size(14).width(Length::Fill),
+                        text(state).size(10),
+                    ]
+                    .spacing(4)
+                    .align_y(Alignment::Center),
+                )
+                .on_press(AppTrayMessage::WindowListActivate(handle.clone()))
+                .style(move |theme, status| button_style(theme, status, is_selected, 1))
+                .width(Length::Fill)
+                .height(Length::Fill);
+                Element::from(
+                    row![
+                        mouse_area(activate).on_right_press(AppTrayMessage::WindowMenu {
+                            item: self.item.clone(),
+                            handle: handle.clone(),
+                        }),
+                        button(text("✕").size(14))
+                            .on_press(AppTrayMessage::WaylandOut(WaylandOutgoing::Close(handle)))
+                            .style(|theme, status| button_style(theme, status, false, 0))
+                            .height(Length::Fill),
+                    ]
+                    .spacing(2)
+                    .height(ROW_HEIGHT as u16),
+                )
+            });
+        column(rows).padding(PADDING).width(Length::Fill).into()
+    }
+}
+
+/// Keys pressed while the popup has keyboard focus, which the panel asks for while it's open
+pub fn subscription() -> Subscription<AppTrayMessage> {
+    keyboard::on_key_press(|key: Key, modifiers: Modifiers| {
+        let key = match key.as_ref() {
+            Key::Named(Named::ArrowUp) => WindowListKey::Previous,
+            Key::Named(Named::Tab) if modifiers.shift() => WindowListKey::Previous,
+            Key::Named(Named::ArrowDown | Named::Tab) => WindowListKey::Next,
+            Key::Named(Named::Enter | Named::Space) => WindowListKey::Activate,
+            Key::Named(Named::Delete) => WindowListKey::Close,
+            Key::Named(Named::ContextMenu) => WindowListKey::Menu,
+            Key::Named(Named::Escape) => WindowListKey::Dismiss,
+            _ => return None,
+        };
+        Some(AppTrayMessage::WindowListKey(key))
+    })
+}
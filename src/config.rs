@@ -27,6 +27,11 @@ pub struct InnerConfig {
     pub app_tray: AppTrayConfig,
     pub icon_theme: Option<IconTheme>,
     pub use_winit: Option<bool>,
+    /// Name of the output whose workspaces the switcher shows, e.g. `DP-1`. Every output's
+    /// when unset
+    pub output: Option<String>,
+    /// Terminal emulator for `Terminal=true` entries, detected when unset
+    pub terminal: Option<String>,
 }
 
 impl PanelConfig {
@@ -66,12 +71,104 @@ impl PanelConfig {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AppTrayConfig {
     pub favorites: Vec<String>,
+    /// How running apps that aren't favorites are ordered
+    #[serde(default)]
+    pub unpinned_order: UnpinnedOrder,
+    /// When windows of the same app share a single button
+    #[serde(default)]
+    pub grouping: Grouping,
+    /// Show window titles next to the icons of running apps
+    #[serde(default)]
+    pub show_titles: bool,
+    /// What clicking and scrolling on tray items does
+    #[serde(default)]
+    pub mouse_bindings: MouseBindings,
 }
 
 impl<'a> Default for AppTrayConfig {
     fn default() -> Self {
         Self {
             favorites: vec!["org.mozilla.firefox".to_string()],
+            unpinned_order: UnpinnedOrder::default(),
+            grouping: Grouping::default(),
+            show_titles: false,
+            mouse_bindings: MouseBindings::default(),
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnpinnedOrder {
+    /// Where the app's oldest open window appeared
+    #[default]
+    FirstOpened,
+    /// Most recently focused first
+    LastFocused,
+    /// By the app's name
+    Alphabetical,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Grouping {
+    #[default]
+    Always,
+    /// One button per window
+    Never,
+    /// Only group apps with more than this many windows open
+    AboveCount(usize),
+}
+
+impl Grouping {
+    pub fn groups(&self, num_windows: usize) -> bool {
+        match self {
+            Grouping::Always => true,
+            Grouping::Never => false,
+            Grouping::AboveCount(count) => num_windows > *count,
+        }
+    }
+}
+
+/// Action for each way of clicking or scrolling on a tray item, unset ones keep their default
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseBindings {
+    pub left: TrayAction,
+    pub middle: TrayAction,
+    pub right: TrayAction,
+    /// Only seen when the compositor tells the panel about held modifiers, which most only do
+    /// while the panel has keyboard focus. A plain left click otherwise
+    pub shift_left: TrayAction,
+    pub scroll_up: TrayAction,
+    pub scroll_down: TrayAction,
+}
+
+impl Default for MouseBindings {
+    fn default() -> Self {
+        Self {
+            left: TrayAction::Toggle,
+            middle: TrayAction::Launch,
+            right: TrayAction::ContextMenu,
+            shift_left: TrayAction::Launch,
+            scroll_up: TrayAction::None,
+            scroll_down: TrayAction::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrayAction {
+    None,
+    /// Start a new instance of the app
+    Launch,
+    /// Focus or minimize the window, list them when there are several. Starts the app when
+    /// it has no windows
+    Toggle,
+    /// Focus the app's next window. Starts the app when it has no windows
+    CycleWindows,
+    MinimizeAll,
+    /// Close every window the item stands for
+    Close,
+    /// List the app's windows. Starts the app when it has no windows
+    WindowList,
+    ContextMenu,
+}
@@ -1,18 +1,39 @@
+use std::time::Duration;
+
 use iced::{
     border::Radius,
     widget::{
         button::{self},
-        rule,
+        container, progress_bar, rule,
     },
     Background, Border, Theme,
 };
 
 pub const PANEL_SIZE: u32 = 48;
 
+/// Extra width tray buttons get when they show a title
+pub const APP_TRAY_LABEL_WIDTH: u32 = 160;
+
+/// Space at either end of the panel
+pub const PANEL_PADDING: f32 = 16.0;
+
 pub const BUTTON_RADIUS: u16 = 8;
 
 pub const APP_TRAY_RULE_THICKNESS: u16 = 3;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LaunchFeedback {
+    None,
+    /// Launched but no window yet, with the pulse animation's progress from 0 to 1
+    Pending(f32),
+    Failed,
+}
+
+/// Progress of a pulse animation after `elapsed`, from 0 to 1 and back once a second
+pub fn pulse(elapsed: Duration) -> f32 {
+    (elapsed.as_secs_f32() * std::f32::consts::PI).sin().abs()
+}
+
 pub fn button_style(
     theme: &Theme,
     status: button::Status,
@@ -49,6 +70,38 @@ pub fn button_style(
     button_theme
 }
 
+pub fn app_tray_button_style(
+    theme: &Theme,
+    status: button::Status,
+    is_active: bool,
+    num_toplevels: usize,
+    feedback: LaunchFeedback,
+    urgency: Option<f32>,
+) -> button::Style {
+    let mut button_theme = button_style(theme, status, is_active, num_toplevels);
+    // A window wanting attention pulses until it's focused, with the animation's progress
+    if let Some(progress) = urgency {
+        let mut background_color = theme.palette().primary;
+        background_color.a = 0.1 + 0.3 * progress;
+        button_theme.background = Some(Background::Color(background_color));
+        button_theme.border.color = theme.palette().primary;
+        button_theme.border.width = 2.0;
+    }
+    match feedback {
+        LaunchFeedback::None => {}
+        LaunchFeedback::Pending(progress) => {
+            let mut background_color = theme.palette().primary;
+            background_color.a = 0.05 + 0.15 * progress;
+            button_theme.background = Some(Background::Color(background_color));
+        }
+        LaunchFeedback::Failed => {
+            button_theme.border.color = theme.palette().danger;
+            button_theme.border.width = 2.0;
+        }
+    }
+    button_theme
+}
+
 pub fn app_tray_icon_rule(theme: &Theme, transparent: bool) -> rule::Style {
     iced::widget::rule::Style {
         color: if transparent {
@@ -62,3 +115,42 @@ pub fn app_tray_icon_rule(theme: &Theme, transparent: bool) -> rule::Style {
         fill_mode: iced::widget::rule::FillMode::Full,
     }
 }
+
+pub fn letter_icon_style(theme: &Theme) -> container::Style {
+    let mut background_color = theme.palette().primary;
+    background_color.a = 0.4;
+    container::Style {
+        background: Some(Background::Color(background_color)),
+        text_color: Some(theme.palette().text),
+        border: Border {
+            radius: Radius::from(BUTTON_RADIUS),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+pub fn badge_style(theme: &Theme) -> container::Style {
+    container::Style {
+        background: Some(Background::Color(theme.palette().danger)),
+        text_color: Some(iced::Color::WHITE),
+        border: Border {
+            radius: Radius::from(BUTTON_RADIUS),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+pub fn progress_bar_style(theme: &Theme) -> progress_bar::Style {
+    let mut background_color = theme.palette().text;
+    background_color.a = 0.3;
+    progress_bar::Style {
+        background: Background::Color(background_color),
+        bar: Background::Color(theme.palette().primary),
+        border: Border {
+            radius: Radius::from(2.0),
+            ..Default::default()
+        },
+    }
+}
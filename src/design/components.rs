@@ -1,17 +1,28 @@
 use iced::{
-    widget::{column, Container},
-    Length, Theme,
+    widget::{column, row, stack, Container},
+    Alignment, Length, Theme,
 };
 
 use crate::freedesktop::icons::ImageHandle;
 
-use super::component_theme::app_tray_icon_rule;
+use super::component_theme::{
+    app_tray_icon_rule, badge_style, letter_icon_style, progress_bar_style,
+};
+
+/// Unread count and progress an app shows over its icon
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IconBadges {
+    pub count: Option<i64>,
+    /// From 0 to 1
+    pub progress: Option<f64>,
+}
 
 pub fn app_tray_button<'a, T: 'a>(
     icon_path: Option<ImageHandle>,
     is_active: bool,
     num_toplevels: usize,
     is_start_menu: bool,
+    badges: IconBadges,
 ) -> iced::widget::Button<'a, T> {
     match icon_path {
         Some(path) => iced::widget::button(if is_start_menu {
@@ -19,7 +30,7 @@ pub fn app_tray_button<'a, T: 'a>(
         } else {
             column![
                 app_tray_horizontal_rule(is_active, num_toplevels, true),
-                crate::design::components::app_icon(path),
+                badged_icon(path, badges),
                 app_tray_horizontal_rule(is_active, num_toplevels, false)
             ]
         }),
@@ -32,6 +43,82 @@ pub fn app_tray_button<'a, T: 'a>(
     .padding(if is_start_menu { 2 } else { 4 })
 }
 
+const MAX_LABEL_CHARS: usize = 20;
+
+/// Tray button showing a title next to the icon, like classic taskbars do
+pub fn app_tray_labeled_button<'a, T: 'a>(
+    icon_path: Option<ImageHandle>,
+    label: String,
+    is_active: bool,
+    num_toplevels: usize,
+    badges: IconBadges,
+) -> iced::widget::Button<'a, T> {
+    // Text isn't cut off on its own, so long titles are shortened up front
+    let label = if label.chars().count() > MAX_LABEL_CHARS {
+        let mut label = label.chars().take(MAX_LABEL_CHARS - 1).collect::<String>();
+        label.push('…');
+        label
+    } else {
+        label
+    };
+    let icon = match icon_path {
+        Some(path) => badged_icon(path, badges),
+        None => iced::widget::container(iced::widget::Space::new(Length::Fill, Length::Fill)),
+    };
+    iced::widget::button(column![
+        app_tray_horizontal_rule(is_active, num_toplevels, true),
+        row![
+            icon.width(32),
+            iced::widget::text(label)
+                .size(14)
+                .wrapping(iced::widget::text::Wrapping::None)
+        ]
+        .spacing(8)
+        .align_y(Alignment::Center)
+        .height(Length::Fill),
+        app_tray_horizontal_rule(is_active, num_toplevels, false)
+    ])
+    .width(Length::Fill)
+    .height(Length::Fill)
+    .padding(4)
+}
+
+/// Counts past this are shown as `99+`
+const MAX_BADGE_COUNT: i64 = 99;
+
+/// `app_icon` with a count in the top right corner and a progress bar along the bottom
+fn badged_icon<'a, T: 'a>(image_handle: ImageHandle, badges: IconBadges) -> Container<'a, T> {
+    let mut layers = stack![app_icon(image_handle)];
+    if let Some(progress) = badges.progress {
+        layers = layers.push(
+            iced::widget::container(
+                iced::widget::progress_bar(0.0..=1.0, progress as f32)
+                    .height(4)
+                    .style(progress_bar_style),
+            )
+            .align_bottom(Length::Fill)
+            .padding([0, 2]),
+        );
+    }
+    if let Some(count) = badges.count {
+        let label = if count > MAX_BADGE_COUNT {
+            format!("{}+", MAX_BADGE_COUNT)
+        } else {
+            count.to_string()
+        };
+        layers = layers.push(
+            iced::widget::container(
+                iced::widget::container(iced::widget::text(label).size(10))
+                    .padding([0, 4])
+                    .style(badge_style),
+            )
+            .align_right(Length::Fill)
+            .align_top(Length::Fill),
+        );
+    }
+    iced::widget::container(layers)
+}
+
 fn app_tray_horizontal_rule<'a, T: 'a>(
     is_active: bool,
     num_toplevels: usize,
@@ -47,6 +134,22 @@ fn app_tray_horizontal_rule<'a, T: 'a>(
     .center_x(Length::Fill)
 }
 
+pub fn workspace_button<'a, T: 'a>(
+    label: String,
+    is_active: bool,
+    num_toplevels: usize,
+) -> iced::widget::Button<'a, T> {
+    iced::widget::button(column![
+        iced::widget::container(iced::widget::text(label).size(14.0))
+            .center_x(Length::Fill)
+            .center_y(Length::Fill),
+        app_tray_horizontal_rule(is_active, num_toplevels, false)
+    ])
+    .width(32)
+    .height(Length::Fill)
+    .padding(2)
+}
+
 pub fn app_icon<'a, T>(image_handle: ImageHandle) -> iced::widget::Container<'a, T> {
     match image_handle {
         ImageHandle::Svg(handle) => iced::widget::container(
@@ -61,5 +164,13 @@ pub fn app_icon<'a, T>(image_handle: ImageHandle) -> iced::widget::Container<'a,
                 .width(Length::Fill)
                 .height(Length::Fill),
         ),
+        ImageHandle::Letter(letter) => iced::widget::container(
+            iced::widget::container(iced::widget::text(letter.to_string()).size(16))
+                .center_x(28)
+                .center_y(28)
+                .style(letter_icon_style),
+        )
+        .center_x(Length::Fill)
+        .center_y(Length::Fill),
     }
 }
@@ -20,7 +20,6 @@ impl<'a> EntryInfo<'a> {
     pub fn new(desktop_entry: DesktopEntry<'a>, icon_theme: &IconTheme) -> Self {
         let invisible = desktop_entry.no_display()
             || desktop_entry.name(&get_languages_from_env()).is_none()
-            || desktop_entry.terminal()
             || desktop_entry.exec().is_none();
 
         let icon_path = if invisible {
@@ -16,6 +16,8 @@ pub enum IconTheme {
 pub enum ImageHandle {
     Svg(iced::widget::svg::Handle),
     Image(iced::widget::image::Handle),
+    /// Drawn in place of an icon when there's none to be found
+    Letter(char),
 }
 
 impl ImageHandle {
@@ -26,6 +28,16 @@ impl ImageHandle {
             Self::Image(iced::widget::image::Handle::from_path(path))
         }
     }
+
+    /// Letter icon for `name`, from its first letter or digit
+    pub fn from_name(name: &str) -> Self {
+        let letter = name
+            .chars()
+            .find(|c| c.is_alphanumeric())
+            .and_then(|c| c.to_uppercase().next())
+            .unwrap_or('?');
+        Self::Letter(letter)
+    }
 }
 
 impl Default for IconTheme {
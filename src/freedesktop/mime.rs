@@ -0,0 +1,364 @@
+// Just enough of the shared-mime-info database to tell what type a file is
+// https://specifications.freedesktop.org/shared-mime-info-spec/latest/
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use once_cell::sync::Lazy;
+
+pub static MIME_DATABASE: Lazy<MimeDatabase> = Lazy::new(MimeDatabase::load);
+
+const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+const DIRECTORY_MIME_TYPE: &str = "inode/directory";
+
+#[derive(Clone, Debug)]
+struct Glob {
+    weight: u32,
+    pattern: String,
+    mime_type: String,
+    case_sensitive: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MimeDatabase {
+    globs: Vec<Glob>,
+    aliases: HashMap<String, String>,
+    subclasses: HashMap<String, Vec<String>>,
+}
+
+impl MimeDatabase {
+    /// Read the database from every data directory, later directories have lower precedence
+    pub fn load() -> Self {
+        Self::load_from(&data_dirs())
+    }
+
+    fn load_from(data_dirs: &[PathBuf]) -> Self {
+        let mut database = Self::default();
+        for dir in data_dirs {
+            let dir = dir.join("mime");
+            if let Ok(globs) = fs::read_to_string(dir.join("globs2")) {
+                database.globs.extend(globs.lines().filter_map(parse_glob));
+            }
+            if let Ok(data) = fs::read_to_string(dir.join("aliases")) {
+                for (alias, mime_type) in data.lines().filter_map(|line| line.split_once(' ')) {
+                    database
+                        .aliases
+                        .entry(alias.to_string())
+                        .or_insert_with(|| mime_type.to_string());
+                }
+            }
+            if let Ok(data) = fs::read_to_string(dir.join("subclasses")) {
+                for (child, parent) in data.lines().filter_map(|line| line.split_once(' ')) {
+                    database
+                        .subclasses
+                        .entry(child.to_string())
+                        .or_default()
+                        .push(parent.to_string());
+                }
+            }
+        }
+        // Highest weight first, then the longest pattern, as the spec asks. Case sensitive
+        // globs go before others that would match the same name, `*.C` isn't `*.c`
+        database.globs.sort_by(|a, b| {
+            b.weight
+                .cmp(&a.weight)
+                .then_with(|| b.pattern.len().cmp(&a.pattern.len()))
+                .then_with(|| b.case_sensitive.cmp(&a.case_sensitive))
+        });
+        log::debug!("Loaded {} mime globs", database.globs.len());
+        database
+    }
+
+    /// Mime type of the file at `path`, going by its name
+    pub fn guess(&self, path: &Path) -> String {
+        if path.is_dir() {
+            return DIRECTORY_MIME_TYPE.to_string();
+        }
+        let Some(name) = path.file_name().map(|name| name.to_string_lossy()) else {
+            return DEFAULT_MIME_TYPE.to_string();
+        };
+        let lowercase = name.to_lowercase();
+        self.globs
+            .iter()
+            .find(|glob| {
+                if glob.case_sensitive {
+                    glob_match(&glob.pattern, &name)
+                } else {
+                    glob_match(&glob.pattern.to_lowercase(), &lowercase)
+                }
+            })
+            .map(|glob| glob.mime_type.clone())
+            .unwrap_or_else(|| DEFAULT_MIME_TYPE.to_string())
+    }
+
+    /// Whether `mime_type` is `parent` or a more specific kind of it
+    pub fn is_a(&self, mime_type: &str, parent: &str) -> bool {
+        let mime_type = self.unalias(mime_type);
+        let parent = self.unalias(parent);
+        if mime_type == parent {
+            return true;
+        }
+        // `image/*` style wildcards show up in MimeType= even though the spec doesn't have them
+        if let Some(media) = parent.strip_suffix("/*") {
+            if mime_type.split('/').next() == Some(media) {
+                return true;
+            }
+        }
+        if parent == "text/plain" && mime_type.starts_with("text/") {
+            return true;
+        }
+        if parent == DEFAULT_MIME_TYPE && !mime_type.starts_with("inode/") {
+            return true;
+        }
+        self.subclasses
+            .get(mime_type)
+            .is_some_and(|parents| parents.iter().any(|x| self.is_a(x, parent)))
+    }
+
+    fn unalias<'a>(&'a self, mime_type: &'a str) -> &'a str {
+        self.aliases
+            .get(mime_type)
+            .map(String::as_str)
+            .unwrap_or(mime_type)
+    }
+}
+
+fn data_dirs() -> Vec<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    data_home
+        .into_iter()
+        .chain(data_dirs.split(':').map(PathBuf::from))
+        .collect()
+}
+
+/// A `weight:mime/type:glob[:flags]` line from globs2
+fn parse_glob(line: &str) -> Option<Glob> {
+    if line.starts_with('#') {
+        return None;
+    }
+    let mut fields = line.split(':');
+    let weight = fields.next()?.parse().ok()?;
+    let mime_type = fields.next()?.to_string();
+    let pattern = fields.next()?.to_string();
+    let case_sensitive = fields.next().is_some_and(|flags| flags.contains("cs"));
+    Some(Glob {
+        weight,
+        pattern,
+        mime_type,
+        case_sensitive,
+    })
+}
+
+/// Shell style matching of `name` against `pattern`, supporting `*` and `?`
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // Where to resume when the last `*` has to swallow another character
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A database read from data directories holding the given `globs2`, `aliases` and
+    /// `subclasses` files, in order of precedence
+    fn database(name: &str, dirs: &[[&str; 3]]) -> MimeDatabase {
+        let root = env::temp_dir().join(format!("rbshell-mime-{}-{}", name, std::process::id()));
+        let data_dirs = dirs
+            .iter()
+            .enumerate()
+            .map(|(idx, files)| {
+                let dir = root.join(idx.to_string());
+                fs::create_dir_all(dir.join("mime")).unwrap();
+                for (file, content) in ["globs2", "aliases", "subclasses"].iter().zip(files) {
+                    fs::write(dir.join("mime").join(file), content).unwrap();
+                }
+                dir
+            })
+            .collect::<Vec<_>>();
+        let database = MimeDatabase::load_from(&data_dirs);
+        fs::remove_dir_all(root).unwrap();
+        database
+    }
+
+    #[test]
+    fn glob_matching() {
+        let cases = [
+            ("*.txt", "notes.txt", true),
+            ("*.txt", "notes.txt.bak", false),
+            ("*.txt", ".txt", true),
+            ("*.tar.gz", "archive.tar.gz", true),
+            ("*.tar.gz", "archive.gz", false),
+            ("Makefile", "Makefile", true),
+            ("Makefile", "Makefile.am", false),
+            ("README*", "README.md", true),
+            ("README*", "README", true),
+            ("?.c", "a.c", true),
+            ("?.c", "ab.c", false),
+            ("*a*b", "xaxxb", true),
+            ("*a*b", "xaxxbc", false),
+            ("**", "", true),
+            ("*", "", true),
+            ("?", "", false),
+            ("*.ü", "datei.ü", true),
+        ];
+        for (pattern, name, expected) in cases {
+            assert_eq!(
+                glob_match(pattern, name),
+                expected,
+                "{} against {}",
+                name,
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn glob_lines() {
+        let glob = parse_glob("50:text/x-csrc:*.c").unwrap();
+        assert_eq!(
+            (glob.weight, glob.mime_type.as_str(), glob.pattern.as_str()),
+            (50, "text/x-csrc", "*.c")
+        );
+        assert!(!glob.case_sensitive);
+        assert!(
+            parse_glob("50:text/x-c++src:*.C:cs")
+                .unwrap()
+                .case_sensitive
+        );
+        assert!(parse_glob("# comment").is_none());
+        assert!(parse_glob("heavy:text/plain:*.txt").is_none());
+        assert!(parse_glob("50:text/plain").is_none());
+    }
+
+    #[test]
+    fn guess_by_name() {
+        let database = database(
+            "guess",
+            &[[
+                "50:text/plain:*.txt\n\
+                 50:application/gzip:*.gz\n\
+                 55:application/x-compressed-tar:*.tar.gz\n\
+                 50:text/x-csrc:*.c\n\
+                 50:text/x-c++src:*.C:cs\n\
+                 10:text/x-readme:README*\n",
+                "",
+                "",
+            ]],
+        );
+        let cases = [
+            ("/tmp/notes.txt", "text/plain"),
+            ("/tmp/NOTES.TXT", "text/plain"),
+            ("/tmp/a.tar.gz", "application/x-compressed-tar"),
+            ("/tmp/a.gz", "application/gzip"),
+            ("/tmp/main.c", "text/x-csrc"),
+            ("/tmp/main.C", "text/x-c++src"),
+            ("/tmp/README.txt", "text/plain"),
+            ("/tmp/README", "text/x-readme"),
+            ("/tmp/unknown.xyz", DEFAULT_MIME_TYPE),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(database.guess(Path::new(path)), expected, "{}", path);
+        }
+        assert_eq!(database.guess(&env::temp_dir()), DIRECTORY_MIME_TYPE);
+    }
+
+    #[test]
+    fn aliases_and_subclasses() {
+        let database = database(
+            "is-a",
+            &[
+                [
+                    "",
+                    "application/x-pdf application/pdf\n",
+                    "application/x-shellscript application/x-executable\n",
+                ],
+                [
+                    "",
+                    "application/x-pdf application/x-old-pdf\n\
+                     text/x-sh application/x-shellscript\n",
+                    "application/x-shellscript text/plain\n\
+                     application/vnd.oasis.opendocument.text application/zip\n\
+                     application/zip application/x-archive\n",
+                ],
+            ],
+        );
+        let cases = [
+            ("application/pdf", "application/pdf", true),
+            // Aliases are resolved on both sides, the first data directory wins
+            ("application/x-pdf", "application/pdf", true),
+            ("application/pdf", "application/x-pdf", true),
+            ("application/x-pdf", "application/x-old-pdf", false),
+            // Parents are looked up through every level and every data directory
+            ("application/x-shellscript", "text/plain", true),
+            (
+                "application/x-shellscript",
+                "application/x-executable",
+                true,
+            ),
+            ("text/x-sh", "text/plain", true),
+            (
+                "application/vnd.oasis.opendocument.text",
+                "application/x-archive",
+                true,
+            ),
+            (
+                "application/zip",
+                "application/vnd.oasis.opendocument.text",
+                false,
+            ),
+            // Implicit parents
+            ("text/x-csrc", "text/plain", true),
+            ("image/png", "image/*", true),
+            ("image/png", "video/*", false),
+            ("image/png", DEFAULT_MIME_TYPE, true),
+            (DIRECTORY_MIME_TYPE, DEFAULT_MIME_TYPE, false),
+            ("application/pdf", "text/plain", false),
+        ];
+        for (mime_type, parent, expected) in cases {
+            assert_eq!(
+                database.is_a(mime_type, parent),
+                expected,
+                "{} is a {}",
+                mime_type,
+                parent
+            );
+        }
+    }
+}
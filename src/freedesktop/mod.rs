@@ -1,2 +1,3 @@
 pub mod desktop_entry;
 pub mod icons;
+pub mod mime;
@@ -0,0 +1,202 @@
+// Launching through org.freedesktop.Application for entries with DBusActivatable=true
+// https://specifications.freedesktop.org/desktop-entry-spec/latest/dbus.html
+
+use std::collections::HashMap;
+
+use zbus::{blocking::Connection, zvariant::Value};
+
+use super::{exec, LaunchInfo};
+
+/// Object path an application exports itself at, derived from its desktop file ID
+fn object_path(app_id: &str) -> String {
+    format!("/{}", app_id.replace('.', "/").replace('-', "_"))
+}
+
+/// Activate the application or one of its actions on the session bus, or ask it to
+/// open `info.files`
+pub fn activate(info: &LaunchInfo, token: Option<&str>) -> zbus::Result<()> {
+    activate_on(&Connection::session()?, info, token)
+}
+
+fn activate_on(
+    connection: &Connection,
+    info: &LaunchInfo,
+    token: Option<&str>,
+) -> zbus::Result<()> {
+    let mut platform_data = HashMap::new();
+    if let Some(token) = token {
+        platform_data.insert("activation-token", Value::from(token));
+        platform_data.insert("desktop-startup-id", Value::from(token));
+    }
+    let path = object_path(&info.app_id);
+    log::info!("Activating {} over D-Bus at {}", info.app_id, path);
+    if let Some(action) = info.action.as_ref() {
+        let parameters: Vec<Value> = Vec::new();
+        connection.call_method(
+            Some(info.app_id.as_str()),
+            path.as_str(),
+            Some("org.freedesktop.Application"),
+            "ActivateAction",
+            &(action.as_str(), parameters, platform_data),
+        )?;
+    } else if info.files.is_empty() {
+        connection.call_method(
+            Some(info.app_id.as_str()),
+            path.as_str(),
+            Some("org.freedesktop.Application"),
+            "Activate",
+            &(platform_data,),
+        )?;
+    } else {
+        // Open only takes URIs
+        let uris = info
+            .files
+            .iter()
+            .map(|file| exec::to_uri(file))
+            .collect::<Vec<_>>();
+        connection.call_method(
+            Some(info.app_id.as_str()),
+            path.as_str(),
+            Some("org.freedesktop.Application"),
+            "Open",
+            &(uris, platform_data),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use zbus::{blocking::connection, zvariant::OwnedValue};
+
+    use super::*;
+    use crate::launcher::test_bus::Bus;
+
+    /// Calls the stub application received, as `(method, arguments)`
+    type Calls = Arc<Mutex<Vec<(String, Vec<String>)>>>;
+
+    struct StubApplication {
+        calls: Calls,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Application")]
+    impl StubApplication {
+        fn activate(&self, _platform_data: HashMap<String, OwnedValue>) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(("Activate".to_string(), Vec::new()));
+        }
+
+        fn open(&self, uris: Vec<String>, _platform_data: HashMap<String, OwnedValue>) {
+            self.calls.lock().unwrap().push(("Open".to_string(), uris));
+        }
+
+        fn activate_action(
+            &self,
+            action: String,
+            _parameters: Vec<OwnedValue>,
+            _platform_data: HashMap<String, OwnedValue>,
+        ) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(("ActivateAction".to_string(), vec![action]));
+        }
+    }
+
+    fn info(files: &[&str], action: Option<&str>) -> LaunchInfo {
+        LaunchInfo {
+            app_id: "org.example.Stub".to_string(),
+            exec: "stub %U".to_string(),
+            name: "Stub".to_string(),
+            files: files.iter().map(|file| file.to_string()).collect(),
+            dbus_activatable: true,
+            action: action.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    /// Activate `info` against a stub of the app, returning the calls it got
+    fn activate_stub(info: &LaunchInfo) -> Option<Vec<(String, Vec<String>)>> {
+        let bus = Bus::start()?;
+        let calls = Calls::default();
+        let _app = connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .name("org.example.Stub")
+            .unwrap()
+            .serve_at(
+                "/org/example/Stub",
+                StubApplication {
+                    calls: calls.clone(),
+                },
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        let client = connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .build()
+            .unwrap();
+        activate_on(&client, info, Some("token")).unwrap();
+        let calls = calls.lock().unwrap().clone();
+        Some(calls)
+    }
+
+    #[test]
+    fn object_path_from_app_id() {
+        assert_eq!(object_path("org.example.Stub"), "/org/example/Stub");
+        assert_eq!(object_path("org.example.my-app"), "/org/example/my_app");
+    }
+
+    #[test]
+    fn open_encodes_paths() {
+        let info = info(
+            &[
+                "/home/user/My Files/50% done.txt",
+                "/tmp/ünïcode#1",
+                "https://example.org/a%20b",
+            ],
+            None,
+        );
+        let Some(calls) = activate_stub(&info) else {
+            return;
+        };
+        assert_eq!(
+            calls,
+            [(
+                "Open".to_string(),
+                vec![
+                    "file:///home/user/My%20Files/50%25%20done.txt".to_string(),
+                    "file:///tmp/%C3%BCn%C3%AFcode%231".to_string(),
+                    "https://example.org/a%20b".to_string(),
+                ]
+            )]
+        );
+        // What's sent has to come back out the same when launching through Exec
+        for (file, uri) in info.files.iter().zip(&calls[0].1).take(2) {
+            assert_eq!(exec::to_path(uri).as_ref(), Some(file));
+        }
+    }
+
+    #[test]
+    fn activate_without_files() {
+        let Some(calls) = activate_stub(&info(&[], None)) else {
+            return;
+        };
+        assert_eq!(calls, [("Activate".to_string(), Vec::new())]);
+    }
+
+    #[test]
+    fn activate_action() {
+        let Some(calls) = activate_stub(&info(&[], Some("new-window"))) else {
+            return;
+        };
+        assert_eq!(
+            calls,
+            [("ActivateAction".to_string(), vec!["new-window".to_string()])]
+        );
+    }
+}
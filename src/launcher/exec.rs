@@ -0,0 +1,496 @@
+// Exec key handling as laid out by the Desktop Entry Specification
+// https://specifications.freedesktop.org/desktop-entry-spec/latest/exec-variables.html
+
+use super::{LaunchError, LaunchInfo};
+
+/// Field codes that the spec has deprecated, these are removed from the command line
+const DEPRECATED_CODES: [char; 6] = ['d', 'D', 'n', 'N', 'v', 'm'];
+
+/// The command lines needed to launch `info`, one per process.
+///
+/// `%f` and `%u` only accept a single file, so apps using them are started once per file.
+pub fn commands(info: &LaunchInfo) -> Result<Vec<Vec<String>>, LaunchError> {
+    let args = tokenize(&unescape(&info.exec))
+        .map_err(|e| LaunchError::Exec(info.app_id.clone(), e.to_string()))?;
+    if args.is_empty() {
+        return Err(LaunchError::EmptyExec(info.app_id.clone()));
+    }
+    let commands = if file_args(&args) == FileArgs::Single && info.files.len() > 1 {
+        info.files
+            .iter()
+            .map(|file| expand(&args, info, std::slice::from_ref(file)))
+            .collect()
+    } else {
+        vec![expand(&args, info, &info.files)]
+    };
+    Ok(commands)
+}
+
+/// How many files a command line can be started with, going by its field codes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileArgs {
+    None,
+    /// `%f` or `%u`
+    Single,
+    /// `%F` or `%U`, which have to be arguments of their own
+    Multiple,
+}
+
+/// How many files the `Exec=` value `exec` takes, `FileArgs::None` when it can't be parsed
+pub fn takes_files(exec: &str) -> FileArgs {
+    tokenize(&unescape(exec))
+        .map(|args| file_args(&args))
+        .unwrap_or(FileArgs::None)
+}
+
+fn file_args(args: &[String]) -> FileArgs {
+    if args.iter().any(|arg| arg == "%F" || arg == "%U") {
+        return FileArgs::Multiple;
+    }
+    let single = args.iter().any(|arg| {
+        let mut chars = arg.chars();
+        // Walk the codes in pairs, so the `%f` in a literal `%%f` isn't counted
+        while let Some(c) = chars.next() {
+            if c == '%' && matches!(chars.next(), Some('f' | 'u')) {
+                return true;
+            }
+        }
+        false
+    });
+    if single {
+        FileArgs::Single
+    } else {
+        FileArgs::None
+    }
+}
+
+/// Undo the escapes every desktop entry string value is allowed to have
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => result.push(' '),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('\\') => result.push('\\'),
+            // Anything else is a quoting escape, which tokenizing deals with
+            Some(c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(super) enum TokenizeError {
+    #[error("Unterminated quote")]
+    UnterminatedQuote,
+    #[error("Unexpected end of line after '\\'")]
+    TrailingEscape,
+}
+
+/// Split a command line into arguments, respecting double quotes
+pub(super) fn tokenize(exec: &str) -> Result<Vec<String>, TokenizeError> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '`' | '$' | '\\')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err(TokenizeError::TrailingEscape),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(TokenizeError::UnterminatedQuote),
+                    }
+                }
+            }
+            ' ' | '\t' | '\n' => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(arg) = current.take() {
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+/// Replace field codes in `args`, dropping arguments that only consisted of field codes
+/// that had nothing to expand to
+fn expand(args: &[String], info: &LaunchInfo, files: &[String]) -> Vec<String> {
+    let mut result = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "%F" => result.extend(files.iter().filter_map(|file| to_path(file))),
+            "%U" => result.extend(files.iter().cloned()),
+            "%i" => {
+                if let Some(icon) = info.icon.as_ref() {
+                    result.push("--icon".to_string());
+                    result.push(icon.clone());
+                }
+            }
+            _ => {
+                let expanded = expand_arg(arg, info, files);
+                if !expanded.is_empty() || arg.is_empty() {
+                    result.push(expanded);
+                }
+            }
+        }
+    }
+    result
+}
+
+fn expand_arg(arg: &str, info: &LaunchInfo, files: &[String]) -> String {
+    let mut result = String::with_capacity(arg.len());
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => result.push('%'),
+            Some('f') => {
+                if let Some(path) = files.first().and_then(|file| to_path(file)) {
+                    result.push_str(&path);
+                }
+            }
+            Some('u') => {
+                if let Some(file) = files.first() {
+                    result.push_str(file);
+                }
+            }
+            Some('c') => result.push_str(&info.name),
+            Some('k') => {
+                if let Some(location) = info.location.as_ref() {
+                    result.push_str(location);
+                }
+            }
+            Some(code) if DEPRECATED_CODES.contains(&code) => {}
+            Some(code) => {
+                log::warn!("Invalid field code %{} in Exec for {}", code, info.app_id);
+            }
+            None => {
+                log::warn!("Trailing '%' in Exec for {}", info.app_id);
+            }
+        }
+    }
+    result
+}
+
+/// Local path for `file`, which may be either a path or a URI. Only `file://` URIs have one.
+pub fn to_path(file: &str) -> Option<String> {
+    let Some((scheme, rest)) = file.split_once("://") else {
+        return Some(file.to_string());
+    };
+    if scheme != "file" {
+        log::warn!("{} is not a local file", file);
+        return None;
+    }
+    // Skip the authority, which is either empty or localhost for local files
+    let path = &rest[rest.find('/')?..];
+    Some(percent_decode(path))
+}
+
+/// URI for `file`, which may be either a path or a URI already
+pub fn to_uri(file: &str) -> String {
+    if file.contains("://") {
+        file.to_string()
+    } else {
+        format!("file://{}", percent_encode(file))
+    }
+}
+
+/// Escape everything but unreserved characters and path separators
+fn percent_encode(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                result.push(byte as char)
+            }
+            _ => result.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    result
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let decoded = (bytes[idx] == b'%')
+            .then(|| bytes.get(idx + 1..idx + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(byte) => {
+                result.push(byte);
+                idx += 3;
+            }
+            None => {
+                result.push(bytes[idx]);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(exec: &str, files: &[&str]) -> LaunchInfo {
+        LaunchInfo {
+            app_id: "org.example.App".to_string(),
+            exec: exec.to_string(),
+            name: "Example App".to_string(),
+            icon: Some("example-icon".to_string()),
+            location: Some("/usr/share/applications/org.example.App.desktop".to_string()),
+            files: files.iter().map(|file| file.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn commands_for(exec: &str, files: &[&str]) -> Vec<Vec<String>> {
+        commands(&info(exec, files)).unwrap()
+    }
+
+    #[test]
+    fn quoting_and_escapes() {
+        let cases: [(&str, &[&str]); 9] = [
+            ("app", &["app"]),
+            ("  app   -a\t-b  ", &["app", "-a", "-b"]),
+            (r#"app "two words" plain"#, &["app", "two words", "plain"]),
+            (r#"app "a"b"c""#, &["app", "abc"]),
+            (r#"app """#, &["app", ""]),
+            // Escapes inside quotes are doubled, the string unescaping comes first
+            (r#"app "say \\"hi\\"""#, &["app", r#"say "hi""#]),
+            (r#"app "\\$HOME \\` \\\\""#, &["app", r"$HOME ` \"]),
+            (r#"app "\\x""#, &["app", r"\x"]),
+            (r"app\sarg", &["app", "arg"]),
+        ];
+        for (exec, expected) in cases {
+            assert_eq!(commands_for(exec, &[]), [expected], "Exec={}", exec);
+        }
+    }
+
+    #[test]
+    fn invalid_exec() {
+        for exec in [r#"app "unterminated"#, r#"app "trailing \\"#] {
+            assert!(
+                matches!(commands(&info(exec, &[])), Err(LaunchError::Exec(..))),
+                "Exec={}",
+                exec
+            );
+        }
+        for exec in ["", "   "] {
+            assert!(
+                matches!(commands(&info(exec, &[])), Err(LaunchError::EmptyExec(_))),
+                "Exec={:?}",
+                exec
+            );
+        }
+    }
+
+    #[test]
+    fn literal_percent() {
+        let cases: [(&str, &[&str]); 3] = [
+            ("app 100%%", &["app", "100%"]),
+            ("app %%f", &["app", "%f"]),
+            (r#"app "%%%%""#, &["app", "%%"]),
+        ];
+        for (exec, expected) in cases {
+            assert_eq!(commands_for(exec, &["/tmp/a"]), [expected], "Exec={}", exec);
+        }
+        // A literal %f doesn't take a file, so it doesn't start a process per file either
+        assert_eq!(
+            commands_for("app %%f", &["/tmp/a", "/tmp/b"]),
+            [["app", "%f"]]
+        );
+    }
+
+    #[test]
+    fn file_args_from_codes() {
+        let cases = [
+            ("app", FileArgs::None),
+            ("app %f", FileArgs::Single),
+            ("app --open=%u", FileArgs::Single),
+            ("app %%%f", FileArgs::Single),
+            ("app %F", FileArgs::Multiple),
+            ("app %f %U", FileArgs::Multiple),
+            ("app %%f", FileArgs::None),
+            ("app %%u %%F", FileArgs::None),
+            // %F and %U only count as arguments of their own
+            ("app --files=%F", FileArgs::None),
+            (r#"app "unterminated %f"#, FileArgs::None),
+        ];
+        for (exec, expected) in cases {
+            assert_eq!(takes_files(exec), expected, "Exec={}", exec);
+        }
+    }
+
+    #[test]
+    fn file_codes() {
+        let files = [
+            "/tmp/a b.txt",
+            "file:///tmp/c%20d.txt",
+            "https://example.org/e",
+        ];
+        let cases: [(&str, &[&str], &[&[&str]]); 12] = [
+            // %f and %u take one file, so there's a command per file
+            (
+                "app %f",
+                &files,
+                &[&["app", "/tmp/a b.txt"], &["app", "/tmp/c d.txt"], &["app"]],
+            ),
+            (
+                "app %u",
+                &files,
+                &[
+                    &["app", "/tmp/a b.txt"],
+                    &["app", "file:///tmp/c%20d.txt"],
+                    &["app", "https://example.org/e"],
+                ],
+            ),
+            (
+                "app %F",
+                &files,
+                &[&["app", "/tmp/a b.txt", "/tmp/c d.txt"]],
+            ),
+            (
+                "app %U",
+                &files,
+                &[&[
+                    "app",
+                    "/tmp/a b.txt",
+                    "file:///tmp/c%20d.txt",
+                    "https://example.org/e",
+                ]],
+            ),
+            // A list of files wins over a single one
+            (
+                "app %f %U",
+                &files[..2],
+                &[&[
+                    "app",
+                    "/tmp/a b.txt",
+                    "/tmp/a b.txt",
+                    "file:///tmp/c%20d.txt",
+                ]],
+            ),
+            (
+                "app --open=%f",
+                &files[..1],
+                &[&["app", "--open=/tmp/a b.txt"]],
+            ),
+            ("app %f", &files[..1], &[&["app", "/tmp/a b.txt"]]),
+            // Codes with nothing to expand to go away, along with their argument
+            ("app %f", &[], &[&["app"]]),
+            ("app %u", &[], &[&["app"]]),
+            ("app %F", &[], &[&["app"]]),
+            ("app %U -x", &[], &[&["app", "-x"]]),
+            ("app --open=%u", &[], &[&["app", "--open="]]),
+        ];
+        for (exec, files, expected) in cases {
+            assert_eq!(
+                commands_for(exec, files),
+                expected,
+                "Exec={} files={:?}",
+                exec,
+                files
+            );
+        }
+    }
+
+    #[test]
+    fn entry_codes() {
+        let cases: [(&str, &[&str]); 5] = [
+            ("app %i", &["app", "--icon", "example-icon"]),
+            ("app --name=%c", &["app", "--name=Example App"]),
+            ("app %c", &["app", "Example App"]),
+            (
+                "app %k",
+                &["app", "/usr/share/applications/org.example.App.desktop"],
+            ),
+            // %i only expands as an argument of its own
+            ("app --icon-name=%i", &["app", "--icon-name="]),
+        ];
+        for (exec, expected) in cases {
+            assert_eq!(commands_for(exec, &[]), [expected], "Exec={}", exec);
+        }
+    }
+
+    #[test]
+    fn entry_codes_without_values() {
+        let mut info = info("app %i %k -x", &[]);
+        info.icon = None;
+        info.location = None;
+        assert_eq!(commands(&info).unwrap(), [["app", "-x"]]);
+    }
+
+    #[test]
+    fn deprecated_and_invalid_codes() {
+        let cases: [(&str, &[&str]); 4] = [
+            ("app %d %D %n %N %v %m -x", &["app", "-x"]),
+            ("app --dir=%d", &["app", "--dir="]),
+            ("app %z", &["app"]),
+            ("app trailing%", &["app", "trailing"]),
+        ];
+        for (exec, expected) in cases {
+            assert_eq!(commands_for(exec, &[]), [expected], "Exec={}", exec);
+        }
+    }
+
+    #[test]
+    fn paths_and_uris() {
+        let cases = [
+            ("/tmp/plain", Some("/tmp/plain"), "file:///tmp/plain"),
+            (
+                "file:///tmp/a%20b%25",
+                Some("/tmp/a b%"),
+                "file:///tmp/a%20b%25",
+            ),
+            (
+                "file://localhost/tmp/x",
+                Some("/tmp/x"),
+                "file://localhost/tmp/x",
+            ),
+            ("https://example.org/", None, "https://example.org/"),
+            ("/tmp/ü #1", Some("/tmp/ü #1"), "file:///tmp/%C3%BC%20%231"),
+        ];
+        for (file, path, uri) in cases {
+            assert_eq!(to_path(file).as_deref(), path, "to_path({})", file);
+            assert_eq!(to_uri(file), uri, "to_uri({})", file);
+        }
+        // Encoding and decoding again gives back the path
+        for path in ["/tmp/a b", "/tmp/100%", "/tmp/ü?&=", "/tmp/%41"] {
+            assert_eq!(to_path(&to_uri(path)).as_deref(), Some(path));
+        }
+    }
+}
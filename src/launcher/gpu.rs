@@ -0,0 +1,189 @@
+// GPU selection through switcheroo-control
+// https://gitlab.freedesktop.org/hadess/switcheroo-control
+
+use std::collections::HashMap;
+
+use zbus::{blocking::Connection, zvariant::OwnedValue};
+
+#[zbus::proxy(
+    interface = "net.hadess.SwitcherooControl",
+    default_service = "net.hadess.SwitcherooControl",
+    default_path = "/net/hadess/SwitcherooControl"
+)]
+trait SwitcherooControl {
+    #[zbus(property, name = "GPUs")]
+    fn gpus(&self) -> zbus::Result<Vec<HashMap<String, OwnedValue>>>;
+}
+
+#[derive(Clone, Debug)]
+pub struct Gpu {
+    pub name: String,
+    pub default: bool,
+    /// Variables that make an application render on this GPU
+    pub environment: Vec<(String, String)>,
+}
+
+impl From<HashMap<String, OwnedValue>> for Gpu {
+    fn from(value: HashMap<String, OwnedValue>) -> Self {
+        let name = value
+            .get("Name")
+            .and_then(|name| name.downcast_ref::<String>().ok())
+            .unwrap_or_default();
+        let default = value
+            .get("Default")
+            .and_then(|default| default.downcast_ref::<bool>().ok())
+            .unwrap_or_default();
+        // Environment is a flat list of alternating names and values
+        let environment = value
+            .get("Environment")
+            .and_then(|environment| environment.try_clone().ok())
+            .and_then(|environment| Vec::<String>::try_from(environment).ok())
+            .unwrap_or_default()
+            .chunks_exact(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+        Self {
+            name,
+            default,
+            environment,
+        }
+    }
+}
+
+/// GPUs known to switcheroo-control, in the order it reports them
+pub fn gpus() -> zbus::Result<Vec<Gpu>> {
+    let connection = Connection::system()?;
+    let proxy = SwitcherooControlProxyBlocking::new(&connection)?;
+    Ok(proxy.gpus()?.into_iter().map(Gpu::from).collect())
+}
+
+/// Index of the GPU to use when a dedicated one is asked for
+pub fn dedicated_gpu(gpus: &[Gpu]) -> Option<usize> {
+    gpus.iter().position(|gpu| !gpu.default)
+}
+
+/// Environment to launch with, either for the GPU at `gpu_idx` in `gpus` or for the dedicated
+/// GPU when the application prefers one
+pub fn environment(
+    gpus: &[Gpu],
+    gpu_idx: Option<usize>,
+    prefers_non_default_gpu: bool,
+) -> Vec<(String, String)> {
+    gpu_idx
+        .or_else(|| {
+            prefers_non_default_gpu
+                .then(|| dedicated_gpu(gpus))
+                .flatten()
+        })
+        .and_then(|idx| gpus.get(idx))
+        .map(|gpu| {
+            log::debug!("Launching on {}", gpu.name);
+            gpu.environment.clone()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::zvariant::Value;
+
+    use super::*;
+
+    /// A GPU the way switcheroo-control describes it
+    fn switcheroo_gpu(
+        name: &str,
+        default: bool,
+        environment: &[&str],
+    ) -> HashMap<String, OwnedValue> {
+        let environment = environment
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+        [
+            ("Name", Value::from(name)),
+            ("Default", Value::from(default)),
+            ("Environment", Value::from(environment)),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), OwnedValue::try_from(value).unwrap()))
+        .collect()
+    }
+
+    fn hybrid_gpus() -> Vec<Gpu> {
+        vec![
+            Gpu::from(switcheroo_gpu("Intel", true, &[])),
+            Gpu::from(switcheroo_gpu(
+                "NVIDIA",
+                false,
+                &[
+                    "__NV_PRIME_RENDER_OFFLOAD",
+                    "1",
+                    "__GLX_VENDOR_LIBRARY_NAME",
+                    "nvidia",
+                ],
+            )),
+        ]
+    }
+
+    fn env(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_switcheroo_gpu() {
+        let gpus = hybrid_gpus();
+        assert_eq!(gpus[0].name, "Intel");
+        assert!(gpus[0].default);
+        assert!(gpus[0].environment.is_empty());
+        assert_eq!(gpus[1].name, "NVIDIA");
+        assert!(!gpus[1].default);
+        assert_eq!(
+            gpus[1].environment,
+            env(&[
+                ("__NV_PRIME_RENDER_OFFLOAD", "1"),
+                ("__GLX_VENDOR_LIBRARY_NAME", "nvidia")
+            ])
+        );
+    }
+
+    #[test]
+    fn odd_environment_drops_trailing_name() {
+        let gpu = Gpu::from(switcheroo_gpu("AMD", false, &["DRI_PRIME", "1", "STRAY"]));
+        assert_eq!(gpu.environment, env(&[("DRI_PRIME", "1")]));
+    }
+
+    #[test]
+    fn environment_for_gpu() {
+        let gpus = hybrid_gpus();
+        let nvidia = gpus[1].environment.clone();
+        let cases: [(Option<usize>, bool, Vec<(String, String)>); 6] = [
+            (None, false, Vec::new()),
+            (None, true, nvidia.clone()),
+            (Some(0), true, Vec::new()),
+            (Some(1), false, nvidia),
+            // The list the index came from is gone
+            (Some(2), false, Vec::new()),
+            (Some(2), true, Vec::new()),
+        ];
+        for (gpu_idx, prefers_non_default_gpu, expected) in cases {
+            assert_eq!(
+                environment(&gpus, gpu_idx, prefers_non_default_gpu),
+                expected,
+                "gpu_idx: {:?}, prefers_non_default_gpu: {}",
+                gpu_idx,
+                prefers_non_default_gpu
+            );
+        }
+    }
+
+    #[test]
+    fn no_dedicated_gpu() {
+        let gpus = vec![Gpu::from(switcheroo_gpu("Intel", true, &[]))];
+        assert_eq!(dedicated_gpu(&gpus), None);
+        assert!(environment(&gpus, None, true).is_empty());
+        assert!(environment(&[], None, true).is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+use std::{io, process::Command};
+
+use freedesktop_desktop_entry::{get_languages_from_env, DesktopEntry};
+
+pub mod dbus;
+pub mod exec;
+pub mod gpu;
+mod reaper;
+pub mod scope;
+pub mod terminal;
+#[cfg(test)]
+mod test_bus;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LaunchError {
+    #[error("Exec for {0} is empty")]
+    EmptyExec(String),
+    #[error("Invalid Exec for {0}: {1}")]
+    Exec(String, String),
+    #[error("No terminal emulator found to run {0} in")]
+    NoTerminal(String),
+    #[error("IO: {0}")]
+    IO(#[from] io::Error),
+}
+
+/// Everything needed to launch a desktop entry, so it can be handed to another thread
+#[derive(Clone, Debug, Default)]
+pub struct LaunchInfo {
+    pub app_id: String,
+    /// Raw `Exec=` value, field codes included
+    pub exec: String,
+    /// Translated name, for `%c`
+    pub name: String,
+    /// For `%i`
+    pub icon: Option<String>,
+    /// Location of the desktop file, for `%k`
+    pub location: Option<String>,
+    /// `Path=`, the working directory to run in
+    pub working_dir: Option<String>,
+    /// Paths or URIs to open with the application
+    pub files: Vec<String>,
+    /// `DBusActivatable=true`, start through the session bus instead of Exec unless a GPU has to
+    /// be picked
+    pub dbus_activatable: bool,
+    /// `PrefersNonDefaultGPU=true` or KDE's `X-KDE-RunOnDiscreteGpu=true`
+    pub prefers_non_default_gpu: bool,
+    /// Variables that make the app render on the GPU it's launched on, see [`gpu::environment`]
+    pub gpu_environment: Vec<(String, String)>,
+    /// `Terminal=true`, run inside a terminal emulator
+    pub terminal: bool,
+    /// Terminal emulator from the config, detected when unset
+    pub terminal_emulator: Option<String>,
+    /// `[Desktop Action ...]` to launch instead of the main entry
+    pub action: Option<String>,
+}
+
+impl LaunchInfo {
+    pub fn new(desktop_entry: &DesktopEntry) -> Option<Self> {
+        Some(Self {
+            app_id: desktop_entry.appid.to_string(),
+            exec: desktop_entry.exec()?.to_string(),
+            name: desktop_entry
+                .name(&get_languages_from_env())
+                .map(|name| name.to_string())
+                .unwrap_or_default(),
+            icon: desktop_entry.icon().map(str::to_string),
+            location: Some(desktop_entry.path.display().to_string()),
+            working_dir: desktop_entry
+                .desktop_entry("Path")
+                .filter(|path| !path.is_empty())
+                .map(str::to_string),
+            files: Vec::new(),
+            dbus_activatable: desktop_entry
+                .desktop_entry("DBusActivatable")
+                .is_some_and(|value| value == "true"),
+            prefers_non_default_gpu: ["PrefersNonDefaultGPU", "X-KDE-RunOnDiscreteGpu"]
+                .iter()
+                .any(|key| desktop_entry.desktop_entry(key) == Some("true")),
+            gpu_environment: Vec::new(),
+            terminal: desktop_entry.terminal(),
+            terminal_emulator: None,
+            action: None,
+        })
+    }
+
+    pub fn for_action(desktop_entry: &DesktopEntry, action: &str) -> Option<Self> {
+        let mut info = Self::new(desktop_entry)?;
+        info.exec = desktop_entry.action_exec(action)?.to_string();
+        info.action = Some(action.to_string());
+        Some(info)
+    }
+}
+
+/// Start the application, passing along the activation token so the new window can take focus
+pub fn launch(info: &LaunchInfo, token: Option<&str>) -> Result<(), LaunchError> {
+    // An activated app is started by the bus with its own environment, so it would ignore the
+    // GPU it was meant to run on
+    if info.dbus_activatable && info.gpu_environment.is_empty() {
+        match dbus::activate(info, token) {
+            Ok(()) => return Ok(()),
+            Err(e) => log::warn!(
+                "D-Bus activation failed for {}, falling back to Exec: {}",
+                info.app_id,
+                e
+            ),
+        }
+    }
+    let terminal = if info.terminal {
+        let terminal = terminal::command(info.terminal_emulator.as_deref())
+            .ok_or_else(|| LaunchError::NoTerminal(info.app_id.clone()))?;
+        Some(terminal)
+    } else {
+        None
+    };
+    let systemd = scope::SystemdManager::get();
+    for argv in exec::commands(info)? {
+        let argv = match terminal.as_ref() {
+            Some(terminal) if !argv.is_empty() => terminal.iter().cloned().chain(argv).collect(),
+            _ => argv,
+        };
+        let Some((program, args)) = argv.split_first() else {
+            return Err(LaunchError::EmptyExec(info.app_id.clone()));
+        };
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .envs(info.gpu_environment.iter().cloned());
+        if let Some(working_dir) = info.working_dir.as_ref() {
+            command.current_dir(working_dir);
+        }
+        if let Some(token) = token {
+            command
+                .env("XDG_ACTIVATION_TOKEN", token)
+                .env("DESKTOP_STARTUP_ID", token);
+        }
+        if systemd.is_none() {
+            scope::detach(&mut command);
+        }
+        log::info!("Launching {}: {:?}", info.app_id, command);
+        let child = command.spawn()?;
+        if let Some(systemd) = systemd {
+            if let Err(e) = systemd.start_scope(&info.app_id, child.id()) {
+                log::warn!("Could not start scope for {}: {}", info.app_id, e);
+            }
+        }
+        // When detached this is the intermediate process, which exits right away
+        reaper::reap(child);
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+// Waiting on launched apps from a single thread, so they don't linger as zombies once they exit
+
+use std::{
+    process::Child,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        OnceLock,
+    },
+    thread,
+    time::Duration,
+};
+
+/// How often children are checked on while any are running
+const REAP_INTERVAL: Duration = Duration::from_secs(1);
+
+static REAPER: OnceLock<Sender<Child>> = OnceLock::new();
+
+/// Hand `child` over to be waited on once it exits
+pub fn reap(child: Child) {
+    let sender = REAPER.get_or_init(|| {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || Reaper::default().run(rx));
+        tx
+    });
+    let _ = sender.send(child);
+}
+
+/// Children that haven't exited yet
+#[derive(Default)]
+struct Reaper {
+    children: Vec<Child>,
+}
+
+impl Reaper {
+    /// Wait on every child that exited, keeping the rest. Returns once no children are left and
+    /// no more can come.
+    fn run(mut self, rx: Receiver<Child>) {
+        loop {
+            // Nothing to check on, sleep until there is
+            if self.children.is_empty() {
+                match rx.recv() {
+                    Ok(child) => self.children.push(child),
+                    Err(_) => return,
+                }
+            }
+            self.children.extend(rx.try_iter());
+            self.reap();
+            if !self.children.is_empty() {
+                thread::sleep(REAP_INTERVAL);
+            }
+        }
+    }
+
+    fn reap(&mut self) {
+        self.children.retain_mut(|child| match child.try_wait() {
+            Ok(Some(status)) => {
+                log::debug!("{} exited: {}", child.id(), status);
+                false
+            }
+            Ok(None) => true,
+            Err(e) => {
+                log::warn!("Could not wait on {}: {}", child.id(), e);
+                false
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+
+    /// Reap until `remaining` children are left, giving them some time to exit
+    fn reap_until(reaper: &mut Reaper, remaining: usize) {
+        for _ in 0..100 {
+            reaper.reap();
+            if reaper.children.len() == remaining {
+                return;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        panic!(
+            "{} children left, expected {}",
+            reaper.children.len(),
+            remaining
+        );
+    }
+
+    #[test]
+    fn keeps_running_children() {
+        let mut reaper = Reaper::default();
+        reaper.children.push(Command::new("true").spawn().unwrap());
+        reaper
+            .children
+            .push(Command::new("sleep").arg("30").spawn().unwrap());
+        reap_until(&mut reaper, 1);
+        reaper.children[0].kill().unwrap();
+        reap_until(&mut reaper, 0);
+    }
+
+    #[test]
+    fn run_returns_once_everything_exited() {
+        let (tx, rx) = mpsc::channel();
+        let reaper = thread::spawn(move || Reaper::default().run(rx));
+        for _ in 0..3 {
+            tx.send(Command::new("true").spawn().unwrap()).unwrap();
+        }
+        drop(tx);
+        // Only returns after waiting on all three
+        reaper.join().unwrap();
+    }
+}
@@ -0,0 +1,256 @@
+// Moving launched apps into their own systemd scope, so they don't share the panel's cgroup
+// https://systemd.io/DESKTOP_ENVIRONMENTS/
+
+use std::{
+    collections::hash_map::RandomState, hash::BuildHasher, os::unix::process::CommandExt,
+    process::Command,
+};
+
+use nix::unistd::{fork, setsid, ForkResult};
+use once_cell::sync::Lazy;
+use zbus::{blocking::Connection, zvariant::Value};
+
+const SYSTEMD_NAME: &str = "org.freedesktop.systemd1";
+
+/// Connected on the first launch and kept for every one after it
+static SYSTEMD_MANAGER: Lazy<Option<SystemdManager>> =
+    Lazy::new(
+        || match Connection::session().and_then(SystemdManager::connect_on) {
+            Ok(manager) => Some(manager),
+            Err(e) => {
+                log::debug!("Not launching into systemd scopes: {}", e);
+                None
+            }
+        },
+    );
+
+pub struct SystemdManager {
+    connection: Connection,
+}
+
+impl SystemdManager {
+    /// The user's systemd instance, `None` if it isn't running
+    pub fn get() -> Option<&'static Self> {
+        SYSTEMD_MANAGER.as_ref()
+    }
+
+    /// Talk to systemd over `connection`, failing if it isn't running there
+    fn connect_on(connection: Connection) -> zbus::Result<Self> {
+        let has_owner: bool = connection
+            .call_method(
+                Some("org.freedesktop.DBus"),
+                "/org/freedesktop/DBus",
+                Some("org.freedesktop.DBus"),
+                "NameHasOwner",
+                &(SYSTEMD_NAME,),
+            )?
+            .body()
+            .deserialize()?;
+        if !has_owner {
+            return Err(zbus::Error::Failure(format!(
+                "{} is not running",
+                SYSTEMD_NAME
+            )));
+        }
+        Ok(Self { connection })
+    }
+
+    /// Move `pid` into a new transient scope named after `app_id`
+    pub fn start_scope(&self, app_id: &str, pid: u32) -> zbus::Result<()> {
+        let unit = unit_name(app_id);
+        log::debug!("Moving {} into {}", pid, unit);
+        let properties = vec![
+            ("PIDs", Value::from(vec![pid])),
+            ("CollectMode", Value::from("inactive-or-failed")),
+        ];
+        let aux: Vec<(&str, Vec<(&str, Value)>)> = Vec::new();
+        self.connection.call_method(
+            Some(SYSTEMD_NAME),
+            "/org/freedesktop/systemd1",
+            Some("org.freedesktop.systemd1.Manager"),
+            "StartTransientUnit",
+            &(unit.as_str(), "fail", properties, aux),
+        )?;
+        Ok(())
+    }
+}
+
+/// `app-rbshell-<appid>-<random>.scope`, per the XDG application naming convention
+fn unit_name(app_id: &str) -> String {
+    let random = RandomState::new().hash_one(std::process::id());
+    format!("app-rbshell-{}-{:x}.scope", escape(app_id), random)
+}
+
+/// Dashes separate the parts of the unit name, so any in the app id are escaped
+fn escape(app_id: &str) -> String {
+    app_id
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '_' | ':' => c.to_string(),
+            c => {
+                let mut buf = [0; 4];
+                c.encode_utf8(&mut buf)
+                    .bytes()
+                    .map(|byte| format!("\\x{:02x}", byte))
+                    .collect()
+            }
+        })
+        .collect()
+}
+
+/// Double fork before exec, so the app is reparented away from the panel when there's
+/// no systemd to hand it to
+pub fn detach(command: &mut Command) {
+    // SAFETY: Only async-signal-safe functions are called between fork and exec
+    unsafe {
+        command.pre_exec(|| {
+            match fork()? {
+                ForkResult::Parent { .. } => nix::libc::_exit(0),
+                ForkResult::Child => {
+                    setsid()?;
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use zbus::{
+        blocking::connection,
+        zvariant::{OwnedObjectPath, OwnedValue},
+    };
+
+    use super::*;
+    use crate::launcher::test_bus::Bus;
+
+    /// What the stub manager was asked to start
+    #[derive(Clone, Debug, PartialEq)]
+    struct StartedUnit {
+        name: String,
+        mode: String,
+        pids: Option<Vec<u32>>,
+        collect_mode: Option<String>,
+    }
+
+    struct StubManager {
+        units: Arc<Mutex<Vec<StartedUnit>>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.systemd1.Manager")]
+    impl StubManager {
+        fn start_transient_unit(
+            &self,
+            name: String,
+            mode: String,
+            properties: Vec<(String, OwnedValue)>,
+            _aux: Vec<(String, Vec<(String, OwnedValue)>)>,
+        ) -> OwnedObjectPath {
+            let properties = properties.into_iter().collect::<HashMap<_, _>>();
+            self.units.lock().unwrap().push(StartedUnit {
+                name,
+                mode,
+                pids: properties
+                    .get("PIDs")
+                    .and_then(|pids| pids.try_clone().ok())
+                    .and_then(|pids| Vec::<u32>::try_from(pids).ok()),
+                collect_mode: properties
+                    .get("CollectMode")
+                    .and_then(|mode| mode.downcast_ref::<String>().ok()),
+            });
+            OwnedObjectPath::try_from("/org/freedesktop/systemd1/job/1").unwrap()
+        }
+    }
+
+    fn client(bus: &Bus) -> Connection {
+        connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn escape_app_id() {
+        for (app_id, escaped) in [
+            ("org.gnome.Nautilus", "org.gnome.Nautilus"),
+            ("snap_app:1", "snap_app:1"),
+            ("my-app", "my\\x2dapp"),
+            ("my app/2", "my\\x20app\\x2f2"),
+            ("ünï", "\\xc3\\xbcn\\xc3\\xaf"),
+            ("", ""),
+        ] {
+            assert_eq!(escape(app_id), escaped, "{}", app_id);
+        }
+    }
+
+    #[test]
+    fn unit_name_from_app_id() {
+        for (app_id, prefix) in [
+            ("org.gnome.Nautilus", "app-rbshell-org.gnome.Nautilus-"),
+            ("my-app", "app-rbshell-my\\x2dapp-"),
+        ] {
+            let unit = unit_name(app_id);
+            let random = unit
+                .strip_prefix(prefix)
+                .and_then(|rest| rest.strip_suffix(".scope"))
+                .unwrap_or_else(|| panic!("{} doesn't start with {}", unit, prefix));
+            assert!(!random.is_empty());
+            assert!(random.chars().all(|c| c.is_ascii_hexdigit()), "{}", unit);
+        }
+    }
+
+    #[test]
+    fn start_scope_on_stub() {
+        let Some(bus) = Bus::start() else {
+            return;
+        };
+        let units = Arc::new(Mutex::new(Vec::new()));
+        let _systemd = connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .name(SYSTEMD_NAME)
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/systemd1",
+                StubManager {
+                    units: units.clone(),
+                },
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        let manager = SystemdManager::connect_on(client(&bus)).unwrap();
+        manager.start_scope("org.example.my-app", 4242).unwrap();
+        let units = units.lock().unwrap().clone();
+        assert_eq!(units.len(), 1);
+        assert!(
+            units[0]
+                .name
+                .starts_with("app-rbshell-org.example.my\\x2dapp-"),
+            "{}",
+            units[0].name
+        );
+        assert_eq!(
+            units[0],
+            StartedUnit {
+                name: units[0].name.clone(),
+                mode: "fail".to_string(),
+                pids: Some(vec![4242]),
+                collect_mode: Some("inactive-or-failed".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn connect_without_systemd() {
+        let Some(bus) = Bus::start() else {
+            return;
+        };
+        assert!(SystemdManager::connect_on(client(&bus)).is_err());
+    }
+}
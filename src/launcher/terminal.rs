@@ -0,0 +1,81 @@
+// Finding a terminal emulator to run Terminal=true entries in
+
+use std::{env, path::Path};
+
+use super::exec;
+
+/// Terminal emulators to try, with the arguments that make them run a command
+const KNOWN_TERMINALS: [(&str, &[&str]); 13] = [
+    ("cosmic-term", &["-e"]),
+    ("ptyxis", &["--"]),
+    ("kgx", &["--"]),
+    ("gnome-terminal", &["--"]),
+    ("konsole", &["-e"]),
+    ("foot", &[]),
+    ("kitty", &[]),
+    ("alacritty", &["-e"]),
+    ("wezterm", &["start", "--"]),
+    ("ghostty", &["-e"]),
+    ("xfce4-terminal", &["-x"]),
+    ("tilix", &["-e"]),
+    ("xterm", &["-e"]),
+];
+
+/// Command line prefix that runs whatever follows it in a terminal.
+///
+/// `configured` takes priority, then xdg-terminal-exec, `$TERMINAL` and finally
+/// the first known emulator that is installed. A configured terminal or
+/// `$TERMINAL` can carry arguments of its own, which go before the ones that
+/// make the terminal run a command.
+pub fn command(configured: Option<&str>) -> Option<Vec<String>> {
+    configured
+        .filter(|terminal| !terminal.is_empty())
+        .map(str::to_string)
+        .or_else(|| in_path("xdg-terminal-exec").then(|| "xdg-terminal-exec".to_string()))
+        .or_else(|| {
+            env::var("TERMINAL")
+                .ok()
+                .filter(|terminal| !terminal.is_empty())
+        })
+        .or_else(|| {
+            KNOWN_TERMINALS
+                .iter()
+                .find(|(terminal, _)| in_path(terminal))
+                .map(|(terminal, _)| terminal.to_string())
+        })
+        .map(|terminal| with_exec_args(&terminal))
+}
+
+fn with_exec_args(terminal: &str) -> Vec<String> {
+    let args = match exec::tokenize(terminal) {
+        Ok(args) if !args.is_empty() => args,
+        Ok(_) => vec![terminal.to_string()],
+        Err(e) => {
+            log::warn!("Invalid terminal command {:?}: {}", terminal, e);
+            vec![terminal.to_string()]
+        }
+    };
+    let program = Path::new(&args[0])
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(&args[0]);
+    // xdg-terminal-exec takes the command as is
+    let exec_args: &[&str] = if program == "xdg-terminal-exec" {
+        &[]
+    } else {
+        KNOWN_TERMINALS
+            .iter()
+            .find(|(known, _)| *known == program)
+            .map(|(_, args)| *args)
+            .unwrap_or(&["-e"])
+    };
+    args.iter()
+        .cloned()
+        .chain(exec_args.iter().map(|arg| arg.to_string()))
+        .collect()
+}
+
+fn in_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
@@ -20,12 +20,15 @@ pub mod design;
 pub mod freedesktop;
 
 mod config;
+mod launcher;
 
 mod panel;
 
 pub mod app_tray;
 mod settings_tray;
 pub mod start_menu;
+mod tooltip;
+mod workspace_switcher;
 
 /// ryanabx desktop shell for wayland desktops
 #[derive(Parser, Debug)]
@@ -10,37 +10,58 @@ use iced::{
             layer_surface::{IcedOutput, SctkLayerSurfaceSettings},
             popup::{SctkPopupSettings, SctkPositioner},
         },
-        shell::commands::{layer_surface::get_layer_surface, popup},
+        shell::commands::{
+            layer_surface::{get_layer_surface, set_keyboard_interactivity},
+            popup,
+        },
     },
-    widget::{column, row, text},
-    window::{self, Id, Settings},
-    Element, Length, Padding, Subscription, Task, Theme,
+    widget::{column, row},
+    window::{self, Id},
+    Element, Length, Padding, Point, Rectangle, Subscription, Task, Theme,
+};
+use smithay_client_toolkit::{
+    reexports::protocols::xdg::shell::client::xdg_positioner::{Anchor as PopupAnchor, Gravity},
+    shell::wlr_layer::{Anchor, KeyboardInteractivity},
 };
-use smithay_client_toolkit::shell::wlr_layer::Anchor;
 
 use crate::{
-    app_tray::{AppTray, AppTrayMessage},
+    app_tray::{
+        compositor::{ToplevelHandle, WaylandIncoming},
+        drag::DragEvent,
+        AppTray, AppTrayMessage, TrayItemKey, TRAY_OFFSET,
+    },
     config::PanelConfig,
-    design::component_theme::PANEL_SIZE,
+    design::component_theme::{PANEL_PADDING, PANEL_SIZE},
     freedesktop::{desktop_entry::DesktopEntryCache, icons::IconTheme},
     settings_tray::{SettingsTray, SettingsTrayMessage},
     start_menu::{StartMenu, StartMenuMessage},
+    tooltip::{self, TooltipMessage, TooltipTarget, TooltipUpdate, Tooltips},
+    workspace_switcher::{WorkspaceSwitcher, WorkspaceSwitcherMessage},
 };
 
 pub struct Panel<'a> {
     start_menu: StartMenu<'a>,
     app_tray: AppTray<'a>,
     settings_tray: SettingsTray,
+    workspace_switcher: WorkspaceSwitcher,
     main_window: window::Id,
     popup_window: Option<(window::Id, PopupType)>,
+    tooltips: Tooltips,
+    /// Open tooltip and its lines
+    tooltip_window: Option<(window::Id, Vec<String>)>,
+    /// Where the pointer is along the panel
+    cursor_x: Option<f32>,
     icon_theme: IconTheme,
     config: Arc<Mutex<PanelConfig>>,
 }
 
 #[derive(Clone, Debug)]
 pub enum PopupType {
-    AppTrayContextMenu { _app_id: String },
+    AppTrayContextMenu { item: TrayItemKey },
+    Previews { item: TrayItemKey },
     StartMenu,
+    WindowList { item: TrayItemKey },
+    WindowMenu { handle: ToplevelHandle },
 }
 
 impl<'a> Panel<'a> {
@@ -56,7 +77,8 @@ impl<'a> Panel<'a> {
             let open: Task<Message> = get_layer_surface(SctkLayerSurfaceSettings {
                 id,
                 layer: smithay_client_toolkit::shell::wlr_layer::Layer::Top,
-                // keyboard_interactivity: todo!(),
+                // Only asked for while the window list is open
+                keyboard_interactivity: KeyboardInteractivity::None,
                 pointer_interactivity: true,
                 anchor: Anchor::BOTTOM.union(Anchor::LEFT).union(Anchor::RIGHT),
                 output: IcedOutput::Active,
@@ -90,12 +112,16 @@ impl<'a> Panel<'a> {
                 start_menu: StartMenu::new(desktop_entry_cache.clone()),
                 app_tray: AppTray::new(app_tray_config_clone, desktop_entry_cache.clone()),
                 settings_tray: SettingsTray::new(),
+                workspace_switcher: WorkspaceSwitcher::new(),
                 main_window: id,
                 popup_window: None,
+                tooltips: Tooltips::default(),
+                tooltip_window: None,
+                cursor_x: None,
                 icon_theme,
                 config,
             },
-            open,
+            Task::batch([open, AppTray::init().map(Message::AppTray)]),
         )
     }
 
@@ -108,47 +134,136 @@ impl<'a> Panel<'a> {
             Message::StartMenu(StartMenuMessage::MenuToggle) => {
                 log::debug!("Requested start menu");
                 let id = Id::unique();
-                let task = popup::get_popup(SctkPopupSettings {
-                    parent: self.main_window,
-                    id,
-                    positioner: SctkPositioner {
-                        size: Some((240, 480)),
-                        // size_limits: todo!(),
-                        // anchor_rect: todo!(),
-                        // anchor: todo!(),
-                        // gravity: todo!(),
-                        // constraint_adjustment: todo!(),
-                        // offset: todo!(),
-                        // reactive: todo!(),
-                        ..Default::default()
-                    },
-                    parent_size: None,
-                    grab: true, // What does this do??
-                });
                 // let (_, task) = window::open(Settings {
                 //     position: window::Position::Centered,
                 //     size: Size::new(240.0, 480.0),
                 //     ..Default::default()
                 // });
-                task.map(move |i| Message::OpenPopup(i, PopupType::StartMenu))
+                self.open_popup(
+                    SctkPopupSettings {
+                        parent: self.main_window,
+                        id,
+                        positioner: SctkPositioner {
+                            size: Some((240, 480)),
+                            // size_limits: todo!(),
+                            // anchor_rect: todo!(),
+                            // anchor: todo!(),
+                            // gravity: todo!(),
+                            // constraint_adjustment: todo!(),
+                            // offset: todo!(),
+                            // reactive: todo!(),
+                            ..Default::default()
+                        },
+                        parent_size: None,
+                        grab: true, // What does this do??
+                    },
+                    PopupType::StartMenu,
+                )
+            }
+            Message::StartMenu(StartMenuMessage::Launch(app_id)) => {
+                log::info!("Requested to launch {}", app_id);
+                // Launch through the app tray so it goes through the same path as favorites
+                let launch = self
+                    .app_tray
+                    .handle_message(AppTrayMessage::Launch(app_id))
+                    .map(Message::AppTray);
+                Task::batch([self.close_popup(), launch])
             }
+            Message::StartMenu(StartMenuMessage::Tooltip(message))
+            | Message::AppTray(AppTrayMessage::Tooltip(message))
+            | Message::SettingsTray(SettingsTrayMessage::Tooltip(message))
+            | Message::Tooltip(message) => self.handle_tooltip(message),
             Message::StartMenu(start_menu_message) => self
                 .start_menu
                 .handle_message(start_menu_message)
                 .map(Message::StartMenu),
-            Message::AppTray(AppTrayMessage::ContextMenu(app_id)) => {
-                let (_, task) = window::open(Settings {
-                    position: window::Position::Centered,
-                    ..Default::default()
+            Message::AppTray(AppTrayMessage::ContextMenu(item)) => {
+                let close = self.close_popup();
+                let Some(settings) =
+                    self.tray_popup_settings(&item, self.app_tray.context_menu_size(&item.app_id))
+                else {
+                    return close;
+                };
+                let open = self.open_popup(settings, PopupType::AppTrayContextMenu { item });
+                Task::batch([close, open])
+            }
+            Message::AppTray(AppTrayMessage::WindowList(_)) if self.app_tray.is_dragging() => {
+                Task::none()
+            }
+            Message::AppTray(AppTrayMessage::WindowList(item)) => {
+                let was_open = self.popup_window.as_ref().is_some_and(|(_, popup_type)| {
+                    matches!(popup_type, PopupType::WindowList { item: open } if *open == item)
                 });
-                task.map(move |i| {
-                    Message::OpenPopup(
-                        i,
-                        PopupType::AppTrayContextMenu {
-                            _app_id: app_id.clone(),
-                        },
-                    )
-                })
+                let close = self.close_popup();
+                // Clicking the tray item again dismisses the list
+                if was_open {
+                    return close;
+                }
+                let size = self.app_tray.open_window_list(item.clone());
+                let Some(settings) = self.tray_popup_settings(&item, size) else {
+                    self.app_tray.close_window_list();
+                    return close;
+                };
+                let open = self.open_popup(settings, PopupType::WindowList { item });
+                // The list is navigated with the keyboard as well
+                let focus = self.keyboard_interactivity(KeyboardInteractivity::OnDemand);
+                Task::batch([close, open, focus])
+            }
+            Message::AppTray(AppTrayMessage::WindowMenu { item, handle }) => {
+                let close = self.close_popup();
+                let Some(settings) =
+                    self.tray_popup_settings(&item, self.app_tray.window_menu_size(&handle))
+                else {
+                    return close;
+                };
+                let open = self.open_popup(settings, PopupType::WindowMenu { handle });
+                Task::batch([close, open])
+            }
+            Message::AppTray(AppTrayMessage::ShowPreviews(item)) => {
+                // Hovering never replaces a popup that was opened on purpose
+                let other_open = self.popup_window.as_ref().is_some_and(|(_, popup_type)| {
+                    !matches!(popup_type, PopupType::Previews { .. })
+                });
+                if other_open || self.app_tray.is_dragging() {
+                    return Task::none();
+                }
+                let close = self.close_popup();
+                let Some(size) = self.app_tray.open_previews(item.clone()) else {
+                    return close;
+                };
+                let Some(mut settings) = self.tray_popup_settings(&item, size) else {
+                    self.app_tray.close_previews();
+                    return close;
+                };
+                // Previews follow the pointer, they mustn't take away input from anything else
+                settings.grab = false;
+                let open = self.open_popup(settings, PopupType::Previews { item });
+                Task::batch([close, open])
+            }
+            Message::AppTray(AppTrayMessage::ClosePopup) => self.close_popup(),
+            // Only the panel itself has tray items to drag
+            Message::AppTray(AppTrayMessage::Drag(id, _)) if id != self.main_window => Task::none(),
+            Message::AppTray(AppTrayMessage::Drag(id, DragEvent::Moved(position))) => {
+                self.cursor_x = Some(position.x);
+                self.app_tray
+                    .handle_message(AppTrayMessage::Drag(
+                        id,
+                        DragEvent::Moved(Point::new(position.x - TRAY_OFFSET, position.y)),
+                    ))
+                    .map(Message::AppTray)
+            }
+            // A click is the end of just looking
+            Message::AppTray(message @ AppTrayMessage::TrayInput { .. }) => {
+                let close = self.close_tooltip();
+                let task = self.app_tray.handle_message(message).map(Message::AppTray);
+                Task::batch([close, task])
+            }
+            Message::AppTray(message @ AppTrayMessage::WaylandIn(WaylandIncoming::Output(_))) => {
+                let task = self.app_tray.handle_message(message).map(Message::AppTray);
+                let configured = self.config.lock().unwrap().inner.output.clone();
+                self.workspace_switcher
+                    .update_output(configured.as_deref(), self.app_tray.backend());
+                task
             }
             Message::AppTray(app_tray_msg) => self
                 .app_tray
@@ -158,21 +273,157 @@ impl<'a> Panel<'a> {
                 .settings_tray
                 .handle_message(settings_tray_msg)
                 .map(Message::SettingsTray),
-            Message::OpenPopup(id, popup_info) => {
-                log::debug!("Popup opened! {:?}", id);
-                let task = if let Some((popup, popup_type)) = self.popup_window.take() {
-                    match popup_type {
-                        PopupType::AppTrayContextMenu { .. } => iced::window::close(popup),
-                        PopupType::StartMenu => popup::destroy_popup(id),
-                    }
+            Message::WorkspaceSwitcher(workspace_switcher_msg) => self
+                .workspace_switcher
+                .handle_message(workspace_switcher_msg, self.app_tray.backend_mut())
+                .map(Message::WorkspaceSwitcher),
+            Message::None => Task::none(),
+        }
+    }
+
+    /// Popup anchored above the tray item `item`
+    fn tray_popup_settings(
+        &self,
+        item: &TrayItemKey,
+        size: (u32, u32),
+    ) -> Option<SctkPopupSettings> {
+        let item_rect = self.app_tray.item_rect(item)?;
+        Some(SctkPopupSettings {
+            parent: self.main_window,
+            id: Id::unique(),
+            positioner: SctkPositioner {
+                size: Some(size),
+                anchor_rect: Rectangle {
+                    x: item_rect.x + TRAY_OFFSET as i32,
+                    ..item_rect
+                },
+                anchor: PopupAnchor::Top,
+                gravity: Gravity::Top,
+                ..Default::default()
+            },
+            parent_size: None,
+            grab: true,
+        })
+    }
+
+    /// Replace whatever popup is open with a new xdg_popup
+    fn open_popup(&mut self, settings: SctkPopupSettings, popup_type: PopupType) -> Task<Message> {
+        let close = self.close_popup();
+        let close_tooltip = self.close_tooltip();
+        log::debug!("Popup opened! {:?}", settings.id);
+        self.popup_window = Some((settings.id, popup_type));
+        Task::batch([close, close_tooltip, popup::get_popup(settings)])
+    }
+
+    fn handle_tooltip(&mut self, message: TooltipMessage) -> Task<Message> {
+        match self.tooltips.handle_message(message) {
+            TooltipUpdate::Show(target) => self.open_tooltip(target),
+            TooltipUpdate::Hide(task) => {
+                Task::batch([self.close_tooltip(), task.map(Message::Tooltip)])
+            }
+            TooltipUpdate::None => Task::none(),
+        }
+    }
+
+    /// Show the tooltip for `target` above it, unless a popup is already saying more
+    fn open_tooltip(&mut self, target: TooltipTarget) -> Task<Message> {
+        if self.popup_window.is_some() || self.app_tray.is_dragging() {
+            return Task::none();
+        }
+        let widget_rect = |x: f32, width: u32| Rectangle {
+            x: x as i32,
+            y: 0,
+            width: width as i32,
+            height: PANEL_SIZE as i32,
+        };
+        let (lines, anchor_rect) = match &target {
+            TooltipTarget::StartMenu => (
+                vec!["Applications".to_string()],
+                widget_rect(PANEL_PADDING, PANEL_SIZE),
+            ),
+            TooltipTarget::TrayItem(item) => {
+                let Some(item_rect) = self.app_tray.item_rect(item) else {
+                    return Task::none();
+                };
+                (
+                    self.app_tray.tooltip(item),
+                    Rectangle {
+                        x: item_rect.x + TRAY_OFFSET as i32,
+                        ..item_rect
+                    },
+                )
+            }
+            // How far right these are depends on the panel's width, so they go by the pointer
+            TooltipTarget::Clock | TooltipTarget::StatusIcons => {
+                let Some(cursor_x) = self.cursor_x else {
+                    return Task::none();
+                };
+                let lines = if target == TooltipTarget::Clock {
+                    self.settings_tray.clock_tooltip()
                 } else {
-                    Task::none()
+                    self.settings_tray.status_icons_tooltip()
                 };
-                self.popup_window = Some((id, popup_info));
-                task
+                (lines, widget_rect(cursor_x, 1))
             }
-            Message::None => Task::none(),
+        };
+        let lines = tooltip::clip(lines);
+        let close = self.close_tooltip();
+        let id = Id::unique();
+        let size = tooltip::size(&lines);
+        self.tooltip_window = Some((id, lines));
+        let open = popup::get_popup(SctkPopupSettings {
+            parent: self.main_window,
+            id,
+            positioner: SctkPositioner {
+                size: Some(size),
+                anchor_rect,
+                anchor: PopupAnchor::Top,
+                gravity: Gravity::Top,
+                ..Default::default()
+            },
+            parent_size: None,
+            grab: false,
+        });
+        Task::batch([close, open])
+    }
+
+    fn close_tooltip(&mut self) -> Task<Message> {
+        match self.tooltip_window.take() {
+            Some((tooltip, _)) => popup::destroy_popup(tooltip),
+            None => Task::none(),
+        }
+    }
+
+    fn close_popup(&mut self) -> Task<Message> {
+        match self.popup_window.take() {
+            Some((popup, PopupType::WindowList { .. })) => {
+                self.app_tray.close_window_list();
+                Task::batch([
+                    popup::destroy_popup(popup),
+                    self.keyboard_interactivity(KeyboardInteractivity::None),
+                ])
+            }
+            Some((popup, PopupType::Previews { .. })) => {
+                self.app_tray.close_previews();
+                popup::destroy_popup(popup)
+            }
+            Some((
+                popup,
+                PopupType::AppTrayContextMenu { .. }
+                | PopupType::StartMenu
+                | PopupType::WindowMenu { .. },
+            )) => popup::destroy_popup(popup),
+            None => Task::none(),
+        }
+    }
+
+    /// Popups only get keyboard focus when the layer surface they belong to can have it, which
+    /// the panel otherwise never asks for so clicking it leaves the active window alone
+    fn keyboard_interactivity(&self, interactivity: KeyboardInteractivity) -> Task<Message> {
+        if self.config.lock().unwrap().inner.use_winit.unwrap_or(false) {
+            return Task::none();
         }
+        set_keyboard_interactivity(self.main_window, interactivity)
     }
 
     pub fn theme(&self, _window: window::Id) -> Theme {
@@ -196,13 +447,16 @@ impl<'a> Panel<'a> {
                     )
                     .map(Message::StartMenu),
                 self.app_tray.view().map(Message::AppTray),
+                self.workspace_switcher
+                    .view(self.app_tray.backend())
+                    .map(Message::WorkspaceSwitcher),
                 self.settings_tray
                     .view(&self.icon_theme)
                     .map(Message::SettingsTray)
             ]
             .padding(Padding {
-                right: 16.0,
-                left: 16.0,
+                right: PANEL_PADDING,
+                left: PANEL_PADDING,
                 top: 0.0,
                 bottom: 0.0,
             });
@@ -218,10 +472,26 @@ impl<'a> Panel<'a> {
             .width(Length::Fill)
             .height(Length::Fill)
             .into()
+        } else if let Some((_, lines)) = self
+            .tooltip_window
+            .as_ref()
+            .filter(|(tooltip, _)| *tooltip == window)
+        {
+            tooltip::view(lines)
         } else if let Some(popup_window) = &self.popup_window.as_ref() {
             match &popup_window.1 {
-                PopupType::AppTrayContextMenu { .. } => text!("Hey").into(),
+                PopupType::AppTrayContextMenu { item } => self
+                    .app_tray
+                    .view_context_menu(&item.app_id)
+                    .map(Message::AppTray),
+                PopupType::Previews { .. } => self.app_tray.view_previews().map(Message::AppTray),
                 PopupType::StartMenu => self.start_menu.view_popup().map(Message::StartMenu),
+                PopupType::WindowList { .. } => {
+                    self.app_tray.view_window_list().map(Message::AppTray)
+                }
+                PopupType::WindowMenu { handle } => {
+                    self.app_tray.view_window_menu(handle).map(Message::AppTray)
+                }
             }
         } else {
             iced::widget::horizontal_space().into()
@@ -241,6 +511,7 @@ pub enum Message {
     StartMenu(StartMenuMessage),
     AppTray(AppTrayMessage),
     SettingsTray(SettingsTrayMessage),
-    OpenPopup(window::Id, PopupType),
+    WorkspaceSwitcher(WorkspaceSwitcherMessage),
+    Tooltip(TooltipMessage),
     None,
 }
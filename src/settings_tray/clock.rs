@@ -49,6 +49,11 @@ impl Clock {
         .into()
     }
 
+    /// Today's date written out, e.g. Saturday, October 18, 2026
+    pub fn full_date(&self) -> String {
+        Local::now().format("%A, %B %-d, %Y").to_string()
+    }
+
     pub fn subscription(&self) -> iced::Subscription<ClockMessage> {
         iced::Subscription::run(|| {
             iced::stream::channel(0, |mut output| async move {
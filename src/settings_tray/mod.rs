@@ -1,8 +1,14 @@
 use clock::{Clock, ClockMessage};
-use iced::{widget::row, Length, Task};
+use iced::{
+    widget::{mouse_area, row},
+    Element, Length, Task,
+};
 use status_icons::StatusIcons;
 
-use crate::freedesktop::icons::IconTheme;
+use crate::{
+    freedesktop::icons::IconTheme,
+    tooltip::{TooltipMessage, TooltipTarget},
+};
 
 mod clock;
 mod status_icons;
@@ -16,6 +22,7 @@ pub struct SettingsTray {
 #[derive(Clone, Debug)]
 pub enum SettingsTrayMessage {
     Clock(ClockMessage),
+    Tooltip(TooltipMessage),
 }
 
 impl SettingsTray {
@@ -32,13 +39,32 @@ impl SettingsTray {
                 .clock
                 .handle_message(clock_msg)
                 .map(SettingsTrayMessage::Clock),
+            // Handled by the panel
+            message @ SettingsTrayMessage::Tooltip(_) => {
+                log::warn!("Settings tray got a panel message: {:?}", message);
+                Task::none()
+            }
         }
     }
 
+    pub fn clock_tooltip(&self) -> Vec<String> {
+        vec![self.clock.full_date()]
+    }
+
+    pub fn status_icons_tooltip(&self) -> Vec<String> {
+        status_icons::connection_details()
+    }
+
     pub fn view(&self, icon_theme: &IconTheme) -> iced::Element<SettingsTrayMessage> {
         iced::widget::container(row![
-            self.status_icons.view(icon_theme),
-            self.clock.view().map(SettingsTrayMessage::Clock),
+            with_tooltip(
+                self.status_icons.view(icon_theme),
+                TooltipTarget::StatusIcons
+            ),
+            with_tooltip(
+                self.clock.view().map(SettingsTrayMessage::Clock),
+                TooltipTarget::Clock
+            ),
         ])
         .center_y(Length::Fill)
         .width(Length::Fill)
@@ -50,3 +76,15 @@ impl SettingsTray {
         self.clock.subscription().map(SettingsTrayMessage::Clock)
     }
 }
+
+fn with_tooltip<'a>(
+    content: impl Into<Element<'a, SettingsTrayMessage>>,
+    target: TooltipTarget,
+) -> Element<'a, SettingsTrayMessage> {
+    mouse_area(content)
+        .on_enter(SettingsTrayMessage::Tooltip(TooltipMessage::Enter(
+            target.clone(),
+        )))
+        .on_exit(SettingsTrayMessage::Tooltip(TooltipMessage::Leave(target)))
+        .into()
+}
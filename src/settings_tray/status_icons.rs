@@ -1,3 +1,7 @@
+use std::{net::IpAddr, path::Path};
+
+use nix::{ifaddrs::getifaddrs, net::if_::InterfaceFlags};
+
 use crate::{
     design::components::app_icon,
     freedesktop::icons::{default_icon_path, network_icon, IconTheme, ImageHandle},
@@ -18,3 +22,86 @@ impl StatusIcons {
         iced::widget::row![app_icon(ImageHandle::from_path(&icon_path.unwrap()))].into()
     }
 }
+
+/// One line per connected network interface, with its addresses
+pub fn connection_details() -> Vec<String> {
+    let addresses = match getifaddrs() {
+        Ok(addresses) => addresses,
+        Err(e) => {
+            log::warn!("Could not list network interfaces: {}", e);
+            return vec!["Network status unavailable".to_string()];
+        }
+    };
+    // Every address of an interface comes as its own entry
+    let mut interfaces: Vec<(String, InterfaceFlags, Vec<IpAddr>)> = Vec::new();
+    for address in addresses {
+        let ip = address.address.as_ref().and_then(|address| {
+            address
+                .as_sockaddr_in()
+                .map(|address| IpAddr::V4(address.ip()))
+                .or_else(|| {
+                    address
+                        .as_sockaddr_in6()
+                        .map(|address| IpAddr::V6(address.ip()))
+                })
+        });
+        let idx = match interfaces
+            .iter()
+            .position(|(name, _, _)| *name == address.interface_name)
+        {
+            Some(idx) => idx,
+            None => {
+                interfaces.push((address.interface_name.clone(), address.flags, Vec::new()));
+                interfaces.len() - 1
+            }
+        };
+        if let Some(ip) = ip.filter(|ip| !is_link_local(ip)) {
+            interfaces[idx].2.push(ip);
+        }
+    }
+    let lines = interfaces
+        .into_iter()
+        .filter(|(_, flags, _)| {
+            flags.contains(InterfaceFlags::IFF_UP | InterfaceFlags::IFF_RUNNING)
+                && !flags.contains(InterfaceFlags::IFF_LOOPBACK)
+        })
+        .filter_map(|(name, flags, ips)| {
+            let kind = connection_kind(&name, flags)?;
+            if ips.is_empty() {
+                return Some(format!("{} ({}): no address", kind, name));
+            }
+            let ips = ips
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            Some(format!("{} ({}): {}", kind, name, ips))
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        vec!["Not connected".to_string()]
+    } else {
+        lines
+    }
+}
+
+/// What sort of connection the interface is, `None` for bridges and other virtual interfaces
+fn connection_kind(name: &str, flags: InterfaceFlags) -> Option<&'static str> {
+    let sysfs = Path::new("/sys/class/net").join(name);
+    if sysfs.join("wireless").exists() {
+        Some("Wi-Fi")
+    } else if flags.contains(InterfaceFlags::IFF_POINTOPOINT) {
+        Some("VPN")
+    } else if sysfs.join("device").exists() {
+        Some("Wired")
+    } else {
+        None
+    }
+}
+
+fn is_link_local(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_link_local(),
+        IpAddr::V6(ip) => ip.segments()[0] & 0xffc0 == 0xfe80,
+    }
+}
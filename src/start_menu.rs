@@ -13,18 +13,20 @@ use iced::{
 use crate::{
     design::{
         component_theme::{button_style, PANEL_SIZE},
-        components::{app_icon, app_tray_button},
+        components::{app_icon, app_tray_button, IconBadges},
     },
     freedesktop::{
         desktop_entry::{DesktopEntryCache, EntryInfo},
         icons::{start_menu_icon, IconTheme, ImageHandle},
     },
+    tooltip::{TooltipMessage, TooltipTarget},
 };
 
 #[derive(Clone, Debug)]
 pub enum StartMenuMessage {
     MenuToggle,
     Launch(String),
+    Tooltip(TooltipMessage),
 }
 
 pub struct StartMenu<'a> {
@@ -44,23 +46,38 @@ impl<'a> StartMenu<'a> {
         let start_menu_icon_path = start_menu_icon(icon_theme)
             .as_deref()
             .map(ImageHandle::from_path);
-        iced::widget::container(
-            app_tray_button(start_menu_icon_path, start_menu_opened, 0, true)
-                .on_press(StartMenuMessage::MenuToggle)
-                .style(move |theme, status| button_style(theme, status, start_menu_opened, 0)),
+        let button = iced::widget::container(
+            app_tray_button(
+                start_menu_icon_path,
+                start_menu_opened,
+                0,
+                true,
+                IconBadges::default(),
+            )
+            .on_press(StartMenuMessage::MenuToggle)
+            .style(move |theme, status| button_style(theme, status, start_menu_opened, 0)),
         )
         .width(PANEL_SIZE as u16)
         .height(PANEL_SIZE as u16)
-        .padding(4.0)
-        .into()
+        .padding(4.0);
+        iced::widget::mouse_area(button)
+            .on_enter(StartMenuMessage::Tooltip(TooltipMessage::Enter(
+                TooltipTarget::StartMenu,
+            )))
+            .on_exit(StartMenuMessage::Tooltip(TooltipMessage::Leave(
+                TooltipTarget::StartMenu,
+            )))
+            .into()
     }
 
     pub fn handle_message(&mut self, message: StartMenuMessage) -> Task<StartMenuMessage> {
         match message {
-            StartMenuMessage::MenuToggle => unreachable!(),
-            StartMenuMessage::Launch(app_id) => {
-                log::info!("Requested to launch {}", app_id);
-                Task::none() // TODO: Actually handle this
+            // Handled by the panel
+            message @ (StartMenuMessage::MenuToggle
+            | StartMenuMessage::Launch(_)
+            | StartMenuMessage::Tooltip(_)) => {
+                log::warn!("Start menu got a panel message: {:?}", message);
+                Task::none()
             }
         }
     }
@@ -0,0 +1,117 @@
+// Small popups saying what the pointer rests on, shown after a delay
+
+use std::time::Duration;
+
+use iced::{
+    widget::{column, container, text},
+    Element, Task,
+};
+
+use crate::app_tray::TrayItemKey;
+
+/// How long the pointer has to rest on something before its tooltip shows up
+const TOOLTIP_DELAY: Duration = Duration::from_millis(600);
+const LINE_HEIGHT: u32 = 18;
+const PADDING: u16 = 6;
+/// Longer lines are cut off, window titles can go on forever
+const MAX_CHARS: usize = 60;
+/// Rough width of a character, the popup has to be sized before its text is laid out
+const CHAR_WIDTH: f32 = 7.0;
+
+/// Things in the panel that have a tooltip
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TooltipTarget {
+    StartMenu,
+    TrayItem(TrayItemKey),
+    Clock,
+    StatusIcons,
+}
+
+#[derive(Clone, Debug)]
+pub enum TooltipMessage {
+    Enter(TooltipTarget),
+    Leave(TooltipTarget),
+    /// A delay started by the hover change with this serial ran out
+    Timeout(u64),
+}
+
+/// What the panel should do with its tooltip
+pub enum TooltipUpdate {
+    /// Show the tooltip for this target
+    Show(TooltipTarget),
+    /// Close the open tooltip, then run the task
+    Hide(Task<TooltipMessage>),
+    None,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Tooltips {
+    hovered: Option<TooltipTarget>,
+    /// Bumped on every hover change, so delays that were overtaken can be ignored
+    serial: u64,
+}
+
+impl Tooltips {
+    pub fn handle_message(&mut self, message: TooltipMessage) -> TooltipUpdate {
+        match message {
+            TooltipMessage::Enter(target) => {
+                self.hovered = Some(target);
+                self.serial += 1;
+                let serial = self.serial;
+                TooltipUpdate::Hide(Task::perform(
+                    async_std::task::sleep(TOOLTIP_DELAY),
+                    move |_| TooltipMessage::Timeout(serial),
+                ))
+            }
+            TooltipMessage::Leave(target) => {
+                // Entering the next widget can come before leaving the last one
+                if self.hovered.as_ref() != Some(&target) {
+                    return TooltipUpdate::None;
+                }
+                self.hovered = None;
+                self.serial += 1;
+                TooltipUpdate::Hide(Task::none())
+            }
+            TooltipMessage::Timeout(serial) if serial == self.serial => match &self.hovered {
+                Some(target) => TooltipUpdate::Show(target.clone()),
+                None => TooltipUpdate::None,
+            },
+            TooltipMessage::Timeout(_) => TooltipUpdate::None,
+        }
+    }
+}
+
+/// `lines` cut down to what a tooltip shows
+pub fn clip(lines: Vec<String>) -> Vec<String> {
+    lines
+        .into_iter()
+        .map(|line| {
+            if line.chars().count() > MAX_CHARS {
+                line.chars().take(MAX_CHARS - 1).chain(['…']).collect()
+            } else {
+                line
+            }
+        })
+        .collect()
+}
+
+/// Popup size needed to show `lines`
+pub fn size(lines: &[String]) -> (u32, u32) {
+    let max_chars = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    (
+        (max_chars as f32 * CHAR_WIDTH) as u32 + 2 * PADDING as u32,
+        lines.len().max(1) as u32 * LINE_HEIGHT + 2 * PADDING as u32,
+    )
+}
+
+pub fn view<'a, Message: 'a>(lines: &[String]) -> Element<'a, Message> {
+    container(column(lines.iter().map(|line| {
+        Element::from(text(line.clone()).size(12).height(LINE_HEIGHT as u16))
+    })))
+    .padding(PADDING)
+    .into()
+}
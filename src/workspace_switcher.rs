@@ -0,0 +1,198 @@
+use std::time::{Duration, Instant};
+
+use iced::{mouse::ScrollDelta, widget::row, Element, Length, Task};
+
+use crate::{
+    app_tray::compositor::{
+        CompositorBackend, CompositorWorkspace, CompositorWorkspaceGroup, WaylandOutgoing,
+        WorkspaceGroupHandle, WorkspaceHandle, WorkspaceState,
+    },
+    design::{component_theme::button_style, components::workspace_button},
+};
+
+/// Touchpads scroll in lots of tiny steps, only one of them counts in this long
+const SCROLL_COOLDOWN: Duration = Duration::from_millis(200);
+
+#[derive(Clone, Debug)]
+pub enum WorkspaceSwitcherMessage {
+    Activate(WorkspaceHandle),
+    Scroll(WorkspaceGroupHandle, ScrollDelta),
+    Create(WorkspaceGroupHandle),
+    Remove(WorkspaceHandle),
+}
+
+#[derive(Clone, Debug)]
+pub struct WorkspaceSwitcher {
+    last_scroll: Option<Instant>,
+    /// Name of the output whose workspaces are shown, every output's when `None`
+    output: Option<String>,
+}
+
+impl WorkspaceSwitcher {
+    pub fn new() -> Self {
+        Self {
+            last_scroll: None,
+            output: None,
+        }
+    }
+
+    /// Pick the output to show workspaces for after the outputs changed
+    ///
+    /// Falls back to the first output when `configured` isn't connected.
+    pub fn update_output(&mut self, configured: Option<&str>, backend: &CompositorBackend) {
+        let outputs = backend.outputs();
+        self.output = configured.and_then(|configured| {
+            if outputs.iter().any(|(_, name)| name == configured) {
+                return Some(configured.to_string());
+            }
+            let (_, fallback) = outputs.first()?;
+            log::warn!(
+                "Output {} not found, showing the workspaces of {}",
+                configured,
+                fallback
+            );
+            Some(fallback.clone())
+        });
+    }
+
+    pub fn handle_message(
+        &mut self,
+        message: WorkspaceSwitcherMessage,
+        backend: &mut CompositorBackend,
+    ) -> Task<WorkspaceSwitcherMessage> {
+        let outgoing = match message {
+            WorkspaceSwitcherMessage::Activate(workspace) => {
+                Some(WaylandOutgoing::ActivateWorkspace(workspace))
+            }
+            WorkspaceSwitcherMessage::Scroll(group, delta) => {
+                let y = match delta {
+                    ScrollDelta::Lines { y, .. } | ScrollDelta::Pixels { y, .. } => y,
+                };
+                // Sideways scrolling doesn't say which way to go
+                let now = Instant::now();
+                if y == 0.0
+                    || self
+                        .last_scroll
+                        .is_some_and(|last| now.duration_since(last) < SCROLL_COOLDOWN)
+                {
+                    return Task::none();
+                }
+                self.last_scroll = Some(now);
+                backend
+                    .workspace_groups
+                    .iter()
+                    .find(|x| x.handle == group)
+                    .and_then(|group| next_workspace(group, y < 0.0))
+                    .map(WaylandOutgoing::ActivateWorkspace)
+            }
+            WorkspaceSwitcherMessage::Create(group) => {
+                let name = backend
+                    .workspace_groups
+                    .iter()
+                    .find(|x| x.handle == group)
+                    .map(|group| visible_workspaces(group).count() + 1)
+                    .unwrap_or(1)
+                    .to_string();
+                Some(WaylandOutgoing::CreateWorkspace(group, name))
+            }
+            WorkspaceSwitcherMessage::Remove(workspace) => {
+                Some(WaylandOutgoing::RemoveWorkspace(workspace))
+            }
+        };
+        if let Some(outgoing) = outgoing {
+            backend.handle_outgoing(outgoing);
+        }
+        Task::none()
+    }
+
+    pub fn view<'a>(
+        &'a self,
+        backend: &'a CompositorBackend,
+    ) -> iced::Element<'a, WorkspaceSwitcherMessage> {
+        let outputs = backend.outputs();
+        let on_output = |group: &&CompositorWorkspaceGroup| match self.output.as_deref() {
+            // Groups that aren't tied to an output belong everywhere
+            Some(output_name) if !group.output.is_empty() => group.output.iter().any(|output| {
+                outputs
+                    .iter()
+                    .any(|(x, name)| x == output && name == output_name)
+            }),
+            _ => true,
+        };
+        iced::widget::row(
+            backend
+                .workspace_groups
+                .iter()
+                .filter(on_output)
+                .map(|group| {
+                    let handle = group.handle.clone();
+                    let workspaces = visible_workspaces(group)
+                        .enumerate()
+                        .map(|(idx, workspace)| {
+                            let is_active = workspace.is_active();
+                            let num_toplevels = backend.workspace_toplevels(&workspace.handle);
+                            let label = if workspace.name.is_empty() {
+                                (idx + 1).to_string()
+                            } else {
+                                workspace.name.clone()
+                            };
+                            let button = workspace_button(label, is_active, num_toplevels)
+                                .on_press(WorkspaceSwitcherMessage::Activate(
+                                    workspace.handle.clone(),
+                                ))
+                                .style(move |theme, status| {
+                                    button_style(theme, status, is_active, num_toplevels)
+                                });
+                            // Only offer removing workspaces that are empty
+                            let area = iced::widget::mouse_area(button);
+                            if workspace.can_remove && num_toplevels == 0 {
+                                Element::from(area.on_right_press(
+                                    WorkspaceSwitcherMessage::Remove(workspace.handle.clone()),
+                                ))
+                            } else {
+                                Element::from(area)
+                            }
+                        })
+                        .chain(group.can_create_workspace.then(|| {
+                            Element::from(
+                                workspace_button("+".to_string(), false, 0)
+                                    .on_press(WorkspaceSwitcherMessage::Create(
+                                        group.handle.clone(),
+                                    ))
+                                    .style(|theme, status| button_style(theme, status, false, 0)),
+                            )
+                        }));
+                    Element::from(
+                        iced::widget::mouse_area(row(workspaces).spacing(4).padding(4)).on_scroll(
+                            move |delta| WorkspaceSwitcherMessage::Scroll(handle.clone(), delta),
+                        ),
+                    )
+                }),
+        )
+        .height(Length::Fill)
+        .into()
+    }
+}
+
+fn visible_workspaces(
+    group: &CompositorWorkspaceGroup,
+) -> impl Iterator<Item = &CompositorWorkspace> {
+    group
+        .workspaces
+        .iter()
+        .filter(|workspace| !workspace.state.contains(&WorkspaceState::Hidden))
+}
+
+/// The workspace next to the active one in the group, scrolling down moves forward
+fn next_workspace(group: &CompositorWorkspaceGroup, forward: bool) -> Option<WorkspaceHandle> {
+    let workspaces = visible_workspaces(group).collect::<Vec<_>>();
+    let active = workspaces
+        .iter()
+        .position(|workspace| workspace.is_active())?;
+    let next = if forward {
+        active.checked_add(1).filter(|x| *x < workspaces.len())
+    } else {
+        active.checked_sub(1)
+    }?;
+    Some(workspaces[next].handle.clone())
+}